@@ -0,0 +1,89 @@
+use rand::seq::SliceRandom;
+
+use crate::engine::RngRef;
+
+// Indexed collection of (sample, target) pairs, e.g. pixels and a one-hot label.
+// Not `len`/`get`, which would clash with the slice methods on `Vec` datasets.
+pub trait Dataset {
+    fn sample_count(&self) -> usize;
+
+    fn sample(&self, index: usize) -> (Vec<f64>, Vec<f64>);
+}
+
+impl Dataset for Vec<(Vec<f64>, Vec<f64>)> {
+    fn sample_count(&self) -> usize {
+        return self.len();
+    }
+
+    fn sample(&self, index: usize) -> (Vec<f64>, Vec<f64>) {
+        return self[index].clone();
+    }
+}
+
+// A view of some samples of another dataset, in the order of `indices`.
+pub struct Subset<'a, D: Dataset + ?Sized> {
+    dataset: &'a D,
+    indices: Vec<usize>,
+}
+
+impl<'a, D: Dataset + ?Sized> Subset<'a, D> {
+    pub fn new(dataset: &'a D, indices: Vec<usize>) -> Subset<'a, D> {
+        assert!(indices.iter().all(|index| *index < dataset.sample_count()));
+        return Subset { dataset, indices };
+    }
+
+    pub fn indices(&self) -> &[usize] {
+        return &self.indices;
+    }
+}
+
+impl<'a, D: Dataset + ?Sized> Dataset for Subset<'a, D> {
+    fn sample_count(&self) -> usize {
+        return self.indices.len();
+    }
+
+    fn sample(&self, index: usize) -> (Vec<f64>, Vec<f64>) {
+        return self.dataset.sample(self.indices[index]);
+    }
+}
+
+// Randomly assigns `validation_fraction` of the samples to the validation set and
+// the rest to the training set.
+pub fn train_validation_split<'a, D: Dataset + ?Sized>(
+    dataset: &'a D,
+    validation_fraction: f64,
+    rng: &RngRef,
+) -> (Subset<'a, D>, Subset<'a, D>) {
+    assert!((0.0..=1.0).contains(&validation_fraction));
+    let mut indices: Vec<usize> = (0..dataset.sample_count()).collect();
+    indices.shuffle(&mut *rng.borrow_mut());
+    let validation_size = (dataset.sample_count() as f64 * validation_fraction).round() as usize;
+    let validation = indices.split_off(dataset.sample_count() - validation_size);
+    return (
+        Subset::new(dataset, indices),
+        Subset::new(dataset, validation),
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::engine::seeded_rng;
+
+    #[test]
+    fn test_split_partitions_the_samples() {
+        let samples: Vec<(Vec<f64>, Vec<f64>)> =
+            (0..10).map(|i| (vec![i as f64], vec![0.0])).collect();
+        let (train, validation) = train_validation_split(&samples, 0.3, &seeded_rng(1));
+        assert_eq!((train.sample_count(), validation.sample_count()), (7, 3));
+
+        let mut all: Vec<usize> = train.indices().to_vec();
+        all.extend_from_slice(validation.indices());
+        all.sort();
+        assert_eq!(all, (0..10).collect::<Vec<usize>>());
+        assert_eq!(validation.sample(0).0[0], validation.indices()[0] as f64);
+
+        let (again, _) = train_validation_split(&samples, 0.3, &seeded_rng(1));
+        assert_eq!(again.indices(), train.indices());
+    }
+}
@@ -0,0 +1,167 @@
+use rand::seq::SliceRandom;
+
+use crate::data::Dataset;
+use crate::engine::RngRef;
+
+pub struct Batch {
+    pub samples: Vec<Vec<f64>>,
+    pub targets: Vec<Vec<f64>>,
+}
+
+impl Batch {
+    pub fn len(&self) -> usize {
+        return self.samples.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.samples.is_empty();
+    }
+}
+
+// Yields mini-batches of a dataset. With `shuffle` every epoch visits the samples
+// in a new order drawn from `rng`; with `drop_last` a final incomplete batch is
+// skipped.
+pub struct DataLoader<'a, D: Dataset + ?Sized> {
+    dataset: &'a D,
+    batch_size: usize,
+    shuffle: bool,
+    drop_last: bool,
+    rng: RngRef,
+}
+
+impl<'a, D: Dataset + ?Sized> DataLoader<'a, D> {
+    pub fn new(dataset: &'a D, batch_size: usize, rng: &RngRef) -> DataLoader<'a, D> {
+        return DataLoader::with_config(dataset, batch_size, true, false, rng);
+    }
+
+    pub fn with_config(
+        dataset: &'a D,
+        batch_size: usize,
+        shuffle: bool,
+        drop_last: bool,
+        rng: &RngRef,
+    ) -> DataLoader<'a, D> {
+        assert!(batch_size > 0);
+        return DataLoader {
+            dataset,
+            batch_size,
+            shuffle,
+            drop_last,
+            rng: rng.clone(),
+        };
+    }
+
+    // Number of batches per epoch.
+    pub fn len(&self) -> usize {
+        return match self.drop_last {
+            true => self.dataset.sample_count() / self.batch_size,
+            false => self.dataset.sample_count().div_ceil(self.batch_size),
+        };
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.len() == 0;
+    }
+
+    // Starts an epoch, drawing its order from the random stream right away.
+    pub fn batches(&self) -> Batches<'a, D> {
+        let mut order: Vec<usize> = (0..self.dataset.sample_count()).collect();
+        if self.shuffle {
+            order.shuffle(&mut *self.rng.borrow_mut());
+        }
+        if self.drop_last {
+            order.truncate(self.len() * self.batch_size);
+        }
+        return Batches {
+            dataset: self.dataset,
+            order,
+            batch_size: self.batch_size,
+            position: 0,
+        };
+    }
+}
+
+pub struct Batches<'a, D: Dataset + ?Sized> {
+    dataset: &'a D,
+    order: Vec<usize>,
+    batch_size: usize,
+    position: usize,
+}
+
+impl<'a, D: Dataset + ?Sized> Iterator for Batches<'a, D> {
+    type Item = Batch;
+
+    fn next(&mut self) -> Option<Batch> {
+        if self.position >= self.order.len() {
+            return None;
+        }
+        let end = (self.position + self.batch_size).min(self.order.len());
+        let (samples, targets) = self.order[self.position..end]
+            .iter()
+            .map(|index| self.dataset.sample(*index))
+            .unzip();
+        self.position = end;
+        return Some(Batch { samples, targets });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::engine::seeded_rng;
+
+    fn samples(count: usize) -> Vec<(Vec<f64>, Vec<f64>)> {
+        return (0..count)
+            .map(|i| (vec![i as f64], vec![(i % 2) as f64]))
+            .collect();
+    }
+
+    fn epoch<D: Dataset + ?Sized>(loader: &DataLoader<D>) -> Vec<Vec<f64>> {
+        return loader
+            .batches()
+            .map(|batch| batch.samples.iter().map(|s| s[0]).collect())
+            .collect();
+    }
+
+    #[test]
+    fn test_batches_and_drop_last() {
+        let dataset = samples(10);
+        let rng = seeded_rng(3);
+        let ordered = DataLoader::with_config(&dataset, 4, false, false, &rng);
+        assert_eq!(ordered.len(), 3);
+        assert_eq!(
+            epoch(&ordered),
+            vec![
+                vec![0.0, 1.0, 2.0, 3.0],
+                vec![4.0, 5.0, 6.0, 7.0],
+                vec![8.0, 9.0]
+            ]
+        );
+        let batch = ordered.batches().next().unwrap();
+        assert_eq!(
+            batch.targets,
+            vec![vec![0.0], vec![1.0], vec![0.0], vec![1.0]]
+        );
+
+        let dropped = DataLoader::with_config(&dataset, 4, true, true, &rng);
+        assert_eq!(dropped.len(), 2);
+        let batches = epoch(&dropped);
+        assert_eq!(batches.len(), 2);
+        assert!(batches.iter().all(|batch| batch.len() == 4));
+    }
+
+    #[test]
+    fn test_shuffling_is_seeded() {
+        let dataset = samples(20);
+        let first = DataLoader::new(&dataset, 8, &seeded_rng(5));
+        let second = DataLoader::new(&dataset, 8, &seeded_rng(5));
+        let (a, b) = (epoch(&first), epoch(&second));
+        assert_eq!(a, b);
+        // a new epoch continues the stream, so the order changes
+        assert_ne!(epoch(&first), a);
+
+        let mut seen: Vec<f64> = a.concat();
+        seen.sort_by(|x, y| x.partial_cmp(y).unwrap());
+        assert_eq!(seen, (0..20).map(|i| i as f64).collect::<Vec<f64>>());
+    }
+}
@@ -0,0 +1,9 @@
+mod dataset;
+mod loader;
+
+pub use dataset::train_validation_split;
+pub use dataset::Dataset;
+pub use dataset::Subset;
+pub use loader::Batch;
+pub use loader::Batches;
+pub use loader::DataLoader;
@@ -18,7 +18,6 @@ impl Engine {
             grad: 0.0,
             previous_nodes: vec![Rc::clone(&left), Rc::clone(&right)],
             id: Identifier::default(),
-            has_been_reset: false,
             backward_graph: VecDeque::new(),
         };
         return Rc::new(RefCell::new(v));
@@ -32,7 +31,6 @@ impl Engine {
             grad: 0.0,
             previous_nodes: vec![Rc::clone(&left), Rc::clone(&right)],
             id: Identifier::default(),
-            has_been_reset: false,
             backward_graph: VecDeque::new(),
         };
         return Rc::new(RefCell::new(v));
@@ -51,16 +49,125 @@ impl Engine {
             grad: 0.0,
             previous_nodes: vec![Rc::clone(&node)],
             id: Identifier::default(),
-            has_been_reset: false,
             backward_graph: VecDeque::new(),
         };
         return Rc::new(RefCell::new(v));
     }
 
+    pub fn leaky_relu(node: &ValueRef, slope: f64) -> ValueRef {
+        return Engine::unary(node, Operation::LEAKY_RELU(slope));
+    }
+
+    pub fn elu(node: &ValueRef, alpha: f64) -> ValueRef {
+        return Engine::unary(node, Operation::ELU(alpha));
+    }
+
+    pub fn gelu(node: &ValueRef) -> ValueRef {
+        return Engine::unary(node, Operation::GELU);
+    }
+
+    pub fn exp(node: &ValueRef) -> ValueRef {
+        return Engine::unary(node, Operation::EXP);
+    }
+
+    pub fn log(node: &ValueRef) -> ValueRef {
+        return Engine::unary(node, Operation::LOG);
+    }
+
+    pub fn tanh(node: &ValueRef) -> ValueRef {
+        return Engine::unary(node, Operation::TANH);
+    }
+
+    pub fn sigmoid(node: &ValueRef) -> ValueRef {
+        return Engine::unary(node, Operation::SIGMOID);
+    }
+
+    pub fn softplus(node: &ValueRef) -> ValueRef {
+        return Engine::unary(node, Operation::SOFTPLUS);
+    }
+
+    pub fn silu(node: &ValueRef) -> ValueRef {
+        return Engine::mul(node, &Engine::sigmoid(node));
+    }
+
+    pub fn powf(node: &ValueRef, exponent: f64) -> ValueRef {
+        return Engine::unary(node, Operation::POW(exponent));
+    }
+
+    pub fn div(left: &ValueRef, right: &ValueRef) -> ValueRef {
+        return Engine::mul(left, &Engine::powf(right, -1.0));
+    }
+
+    pub fn max(nodes: &[ValueRef]) -> ValueRef {
+        assert!(!nodes.is_empty());
+        return Engine::node(Operation::MAX, nodes.to_vec());
+    }
+
+    pub fn sum(nodes: &[ValueRef]) -> ValueRef {
+        assert!(!nodes.is_empty());
+        let mut total = nodes[0].clone();
+        for node in nodes.iter().skip(1) {
+            total = Engine::add(&total, node);
+        }
+        return total;
+    }
+
+    // Shifted by the maximum so that large inputs don't overflow `exp`.
+    pub fn softmax(nodes: &[ValueRef]) -> Vec<ValueRef> {
+        let max = Engine::max(nodes);
+        let exps: Vec<ValueRef> = nodes
+            .iter()
+            .map(|node| Engine::exp(&Engine::add(node, &Engine::inv(&max))))
+            .collect();
+        let normalizer = Engine::powf(&Engine::sum(&exps), -1.0);
+        return exps
+            .iter()
+            .map(|exp| Engine::mul(exp, &normalizer))
+            .collect();
+    }
+
+    // Passes the value through but no gradient back.
+    pub fn detach(node: &ValueRef) -> ValueRef {
+        return Engine::unary(node, Operation::DETACH);
+    }
+
+    // Picks `options[index]`, where `index` is read from the value of a node on
+    // every forward pass. Only the picked option receives a gradient.
+    pub fn select(index: &ValueRef, options: &[ValueRef]) -> ValueRef {
+        assert!(!options.is_empty());
+        let mut previous_nodes = vec![Rc::clone(index)];
+        previous_nodes.extend(options.iter().cloned());
+        return Engine::node(Operation::SELECT, previous_nodes);
+    }
+
+    fn unary(node: &ValueRef, operation: Operation) -> ValueRef {
+        return Engine::node(operation, vec![Rc::clone(node)]);
+    }
+
+    // Builds a node for any operation, e.g. when rebuilding a saved graph. The
+    // caller is responsible for passing as many inputs as the operation takes.
+    pub fn node(operation: Operation, previous_nodes: Vec<ValueRef>) -> ValueRef {
+        let mut v = Value {
+            value: 0.0,
+            needs_grad: true,
+            operation,
+            grad: 0.0,
+            previous_nodes,
+            id: Identifier::default(),
+            backward_graph: VecDeque::new(),
+        };
+        v.forward_step();
+        return Rc::new(RefCell::new(v));
+    }
+
     pub fn inv(node: &ValueRef) -> ValueRef {
         return Engine::mul(node, &Value::from(-1.0));
     }
 
+    pub fn abs(node: &ValueRef) -> ValueRef {
+        return Engine::add(&Engine::relu(node), &Engine::relu(&Engine::inv(node)));
+    }
+
     pub fn pow(node: &ValueRef) -> ValueRef {
         return Engine::mul(node, node);
     }
@@ -1,5 +1,6 @@
 mod id;
 mod operation;
+mod random;
 mod value;
 mod engine;
 
@@ -7,3 +8,8 @@ pub use value::Value;
 pub use value::ValueRef;
 pub use value::VALUE_RANDOM_SEED;
 pub use engine::Engine;
+pub use operation::Operation;
+pub use random::default_rng;
+pub use random::randomize;
+pub use random::seeded_rng;
+pub use random::RngRef;
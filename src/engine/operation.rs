@@ -1,9 +1,22 @@
 use std::fmt;
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Operation {
     ADD,
     MUL,
     RELU,
+    LEAKY_RELU(f64),
+    ELU(f64),
+    GELU,
+    EXP,
+    LOG,
+    TANH,
+    SIGMOID,
+    SOFTPLUS,
+    POW(f64),
+    MAX,
+    DETACH,
+    SELECT,
     NONE,
 }
 
@@ -22,6 +35,18 @@ impl fmt::Display for Operation {
                 Operation::ADD => "Add",
                 Operation::MUL => "Mul",
                 Operation::RELU => "Relu",
+                Operation::LEAKY_RELU(_) => "LeakyRelu",
+                Operation::ELU(_) => "Elu",
+                Operation::GELU => "Gelu",
+                Operation::EXP => "Exp",
+                Operation::LOG => "Log",
+                Operation::TANH => "Tanh",
+                Operation::SIGMOID => "Sigmoid",
+                Operation::SOFTPLUS => "Softplus",
+                Operation::POW(_) => "Pow",
+                Operation::MAX => "Max",
+                Operation::DETACH => "Detach",
+                Operation::SELECT => "Select",
                 Operation::NONE => "None",
             }
         );
@@ -0,0 +1,34 @@
+use rand::Rng;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::engine::value::ValueRef;
+use crate::engine::value::VALUE_RANDOM_SEED;
+
+// Random stream shared by the stochastic parts of a model (e.g. dropout masks).
+pub type RngRef = Rc<RefCell<ChaCha8Rng>>;
+
+pub fn seeded_rng(seed: u64) -> RngRef {
+    return Rc::new(RefCell::new(ChaCha8Rng::seed_from_u64(seed)));
+}
+
+// Follows VALUE_RANDOM_SEED, falls back to entropy when no seed was set.
+pub fn default_rng() -> RngRef {
+    let seed = VALUE_RANDOM_SEED.load(std::sync::atomic::Ordering::Relaxed);
+    if seed == 0 {
+        return Rc::new(RefCell::new(ChaCha8Rng::from_entropy()));
+    }
+    return seeded_rng(seed);
+}
+
+// Draws the values of `nodes` uniformly from [-bound, bound] with a stream of their
+// own. Reproducible where VALUE_RANDOM_SEED is not: tests running concurrently
+// share and set that global.
+pub fn randomize(nodes: &[ValueRef], bound: f64, seed: u64) {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    for node in nodes.iter() {
+        node.borrow_mut().value = rng.gen_range(-bound..bound);
+    }
+}
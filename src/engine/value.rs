@@ -13,6 +13,16 @@ use crate::engine::operation::Operation;
 pub type ValueRef = Rc<RefCell<Value>>;
 pub static VALUE_RANDOM_SEED: AtomicU64 = AtomicU64::new(0);
 
+thread_local! {
+    // the stream `Value::random` draws from while VALUE_RANDOM_SEED is set, and its
+    // seed; restarted when the seed changes
+    static SEEDED_RNG: RefCell<Option<(u64, ChaCha8Rng)>> = const { RefCell::new(None) };
+}
+
+// constants of the tanh approximation of GELU
+const GELU_SCALE: f64 = 0.7978845608028654;
+const GELU_CUBIC: f64 = 0.044715;
+
 #[derive(Default)]
 pub struct Value {
     pub value: f64,
@@ -21,22 +31,23 @@ pub struct Value {
     pub id: Identifier,
     pub operation: Operation,
     pub previous_nodes: Vec<ValueRef>,
-    pub has_been_reset: bool,
     pub backward_graph: VecDeque<ValueRef>,
 }
 
 impl Value {
     pub fn random() -> ValueRef {
-        if VALUE_RANDOM_SEED.load(std::sync::atomic::Ordering::Relaxed) == 0 {
+        let seed = VALUE_RANDOM_SEED.load(std::sync::atomic::Ordering::Relaxed);
+        if seed == 0 {
             return Value::from(rand::thread_rng().gen_range(0.0..1.0));
-        } else {
-            return Value::from(
-                ChaCha8Rng::seed_from_u64(
-                    VALUE_RANDOM_SEED.load(std::sync::atomic::Ordering::Relaxed),
-                )
-                .gen_range(0.0..1.0),
-            );
         }
+        return SEEDED_RNG.with(|cell| {
+            let mut state = cell.borrow_mut();
+            if !matches!(*state, Some((current, _)) if current == seed) {
+                *state = Some((seed, ChaCha8Rng::seed_from_u64(seed)));
+            }
+            let (_, rng) = state.as_mut().unwrap();
+            return Value::from(rng.gen_range(0.0..1.0));
+        });
     }
 
     pub fn from(value: f64) -> ValueRef {
@@ -50,10 +61,12 @@ impl Value {
         mut pointers: VecDeque<ValueRef>,
         mut visited: HashSet<Identifier>,
     ) -> (VecDeque<ValueRef>, HashSet<Identifier>) {
-        if !visited.contains(&self.id) {
-            visited.insert(self.id);
-            for node in self.previous_nodes.iter() {
-                (pointers, visited) = node.borrow_mut().backward_recursive(pointers, visited);
+        // every node is queued exactly once, after all of its inputs, so that nodes
+        // with several consumers propagate their gradient only once
+        for node in self.previous_nodes.iter() {
+            let id = node.borrow().id;
+            if visited.insert(id) {
+                (pointers, visited) = node.borrow().backward_recursive(pointers, visited);
                 pointers.push_back(Rc::clone(node));
             }
         }
@@ -66,19 +79,22 @@ impl Value {
             (self.backward_graph, _) = self.backward_recursive(VecDeque::new(), HashSet::new());
         }
 
-        // flush grads if necessary
-        for pointer in self.backward_graph.iter_mut() {
-            pointer.borrow_mut().propagate_zero_grad();
+        // Intermediate gradients only belong to this pass and start from zero, leaves
+        // (parameters and inputs) accumulate across passes until `zero_grad`.
+        for pointer in self.backward_graph.iter() {
+            let mut node = pointer.borrow_mut();
+            if !node.previous_nodes.is_empty() {
+                node.grad = 0.0;
+            }
         }
         self.update_previous();
 
-        for pointer in self.backward_graph.iter_mut().rev() {
-            pointer.borrow_mut().has_been_reset = false;
-            pointer.borrow_mut().update_previous();
+        for pointer in self.backward_graph.iter().rev() {
+            pointer.borrow().update_previous();
         }
     }
 
-    fn forward_step(&mut self) {
+    pub(super) fn forward_step(&mut self) {
         match self.operation {
             Operation::ADD => {
                 self.value =
@@ -94,10 +110,73 @@ impl Value {
                     false => 0.0,
                 };
             }
+            Operation::MAX => {
+                self.value = self
+                    .previous_nodes
+                    .iter()
+                    .map(|node| node.borrow().value)
+                    .fold(f64::NEG_INFINITY, f64::max);
+            }
+            Operation::DETACH => {
+                self.value = self.get_previous_value(0);
+            }
+            Operation::SELECT => {
+                self.value = self.get_previous_value(self.selected_index());
+            }
             Operation::NONE => {}
+            _ => {
+                let x = self.get_previous_value(0);
+                self.value = match self.operation {
+                    Operation::LEAKY_RELU(slope) => match x > 0.0 {
+                        true => x,
+                        false => slope * x,
+                    },
+                    Operation::ELU(alpha) => match x > 0.0 {
+                        true => x,
+                        false => alpha * x.exp_m1(),
+                    },
+                    Operation::GELU => {
+                        0.5 * x * (1.0 + (GELU_SCALE * (x + GELU_CUBIC * x.powi(3))).tanh())
+                    }
+                    Operation::EXP => x.exp(),
+                    Operation::LOG => x.ln(),
+                    Operation::TANH => x.tanh(),
+                    Operation::SIGMOID => 1.0 / (1.0 + (-x).exp()),
+                    Operation::SOFTPLUS => x.max(0.0) + (-x.abs()).exp().ln_1p(),
+                    Operation::POW(exponent) => x.powf(exponent),
+                    _ => unreachable!(),
+                };
+            }
         }
     }
 
+    // Derivative of a single input operation with respect to its input.
+    fn unary_derivative(&self) -> f64 {
+        let x = self.get_previous_value(0);
+        return match self.operation {
+            Operation::LEAKY_RELU(slope) => match x > 0.0 {
+                true => 1.0,
+                false => slope,
+            },
+            Operation::ELU(alpha) => match x > 0.0 {
+                true => 1.0,
+                false => self.value + alpha,
+            },
+            Operation::GELU => {
+                let t = (GELU_SCALE * (x + GELU_CUBIC * x.powi(3))).tanh();
+                0.5 * (1.0 + t)
+                    + 0.5 * x * (1.0 - t * t) * GELU_SCALE * (1.0 + 3.0 * GELU_CUBIC * x * x)
+            }
+            Operation::EXP => self.value,
+            Operation::LOG => 1.0 / x,
+            Operation::TANH => 1.0 - self.value * self.value,
+            Operation::SIGMOID => self.value * (1.0 - self.value),
+            Operation::SOFTPLUS => 1.0 / (1.0 + (-x).exp()),
+            Operation::POW(exponent) => exponent * x.powf(exponent - 1.0),
+            _ => unreachable!(),
+        };
+    }
+
     pub fn forward(&mut self) {
         if self.backward_graph.len() == 0 {
             (self.backward_graph, _) = self.backward_recursive(VecDeque::new(), HashSet::new());
@@ -109,7 +188,25 @@ impl Value {
         self.forward_step();
     }
 
-    fn update_previous(&mut self) {
+    // Recomputes several nodes with one pass over their combined graph, computing
+    // shared nodes once instead of once per output.
+    pub fn forward_all(nodes: &[ValueRef]) {
+        let mut order: VecDeque<ValueRef> = VecDeque::new();
+        let mut visited: HashSet<Identifier> = HashSet::new();
+        for node in nodes.iter() {
+            let id = node.borrow().id;
+            if visited.insert(id) {
+                (order, visited) = node.borrow().backward_recursive(order, visited);
+                order.push_back(Rc::clone(node));
+            }
+        }
+
+        for pointer in order.iter() {
+            pointer.borrow_mut().forward_step();
+        }
+    }
+
+    fn update_previous(&self) {
         match self.operation {
             Operation::ADD => {
                 if self.needs_grad(0) {
@@ -136,10 +233,43 @@ impl Value {
                     };
                 }
             }
+            Operation::MAX => {
+                let index = (0..self.previous_nodes.len())
+                    .find(|index| self.get_previous_value(*index) == self.value);
+                if let Some(index) = index {
+                    if self.needs_grad(index) {
+                        self.update_previous_node(index, self.grad);
+                    }
+                }
+            }
+            // stops the gradient, e.g. for truncated backpropagation through time
+            Operation::DETACH => {}
+            Operation::SELECT => {
+                let index = self.selected_index();
+                if self.needs_grad(index) {
+                    self.update_previous_node(index, self.grad);
+                }
+            }
             Operation::NONE => {}
+            _ => {
+                if self.needs_grad(0) {
+                    self.update_previous_node(0, self.unary_derivative() * self.grad);
+                }
+            }
         }
     }
 
+    // The first input of a select holds the position among the remaining inputs.
+    fn selected_index(&self) -> usize {
+        let position = self.get_previous_value(0);
+        assert!(
+            position >= 0.0 && (position as usize) < self.previous_nodes.len() - 1,
+            "select index {} out of range",
+            position
+        );
+        return position as usize + 1;
+    }
+
     fn needs_grad(&self, index: usize) -> bool {
         return self.previous_nodes[index].borrow().needs_grad;
     }
@@ -154,19 +284,67 @@ impl Value {
 
     pub fn zero_grad(&mut self) {
         self.grad = 0.0;
-        self.has_been_reset = true;
     }
+}
 
-    fn propagate_zero_grad(&mut self) {
-        let mut needs_reset = false;
-        for previous in self.previous_nodes.iter() {
-            if previous.borrow().has_been_reset {
-                needs_reset = true;
-            }
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::engine::Engine;
+
+    #[test]
+    fn test_seeded_random_values_differ() {
+        VALUE_RANDOM_SEED.store(1, std::sync::atomic::Ordering::Relaxed);
+        let values: Vec<f64> = (0..10).map(|_x| Value::random().borrow().value).collect();
+        for (index, value) in values.iter().enumerate() {
+            assert!(!values[..index].contains(value));
         }
-        if needs_reset {
-            self.grad = 0.0;
-            self.has_been_reset = true;
+    }
+
+    #[test]
+    fn test_forward_all_shares_the_graph() {
+        let x = Value::from(2.0);
+        let square = Engine::mul(&x, &x);
+        let outputs = vec![
+            Engine::add(&square, &Value::from(1.0)),
+            square.clone(),
+            Engine::mul(&square, &x),
+        ];
+        x.borrow_mut().value = 3.0;
+        Value::forward_all(&outputs);
+        let values: Vec<f64> = outputs.iter().map(|o| o.borrow().value).collect();
+        assert_eq!(values, vec![10.0, 9.0, 27.0]);
+    }
+
+    #[test]
+    fn test_shared_node_gradient() {
+        let x = Value::from(2.0);
+        x.borrow_mut().needs_grad = true;
+        let a = Engine::mul(&x, &Value::from(3.0));
+        let b = Engine::add(&a, &a);
+        let c = Engine::add(&b, &a);
+        c.borrow_mut().grad = 1.0;
+        c.borrow_mut().backward();
+        assert_eq!(x.borrow().grad, 9.0);
+    }
+
+    #[test]
+    fn test_repeated_backward_accumulates_into_leaves() {
+        let x = Value::from(2.0);
+        x.borrow_mut().needs_grad = true;
+        let square = Engine::mul(&x, &x);
+        let y = Engine::mul(&square, &Value::from(3.0));
+        for _ in 0..3 {
+            y.borrow_mut().grad = 1.0;
+            y.borrow_mut().backward();
+            // intermediate gradients are recomputed, not accumulated
+            assert_eq!(square.borrow().grad, 3.0);
         }
+        assert_eq!(x.borrow().grad, 3.0 * 12.0);
+
+        x.borrow_mut().zero_grad();
+        y.borrow_mut().grad = 0.5;
+        y.borrow_mut().backward();
+        assert_eq!(x.borrow().grad, 6.0);
     }
 }
@@ -1,7 +1,11 @@
+mod data;
 mod engine;
 mod nn;
 mod mnist;
+mod optim;
+mod serialize;
 
+use nn::Module;
 use nn::MLP;
 use engine::Value;
 use engine::Engine;
@@ -1,3 +1,5 @@
 mod read;
 
-pub use read::read_mnist_labels;
\ No newline at end of file
+pub use read::mnist_cnn;
+pub use read::read_mnist_labels;
+pub use read::MnistImage;
@@ -1,6 +1,19 @@
-use crate::engine::Engine;
-use crate::engine::Value;
+use crate::data::train_validation_split;
+use crate::data::DataLoader;
+use crate::data::Dataset;
+use crate::engine::seeded_rng;
+use crate::nn::Activation;
+use crate::nn::ActivationLayer;
+use crate::nn::Conv2d;
+use crate::nn::Layer;
+use crate::nn::Loss;
+use crate::nn::Module;
+use crate::nn::Pool;
+use crate::nn::Reshape;
+use crate::nn::Sequential;
 use crate::nn::MLP;
+use crate::optim::accumulate_gradients;
+use crate::optim::clip_grad_norm;
 use byteorder::{BigEndian, ByteOrder};
 use std::fs;
 
@@ -19,6 +32,53 @@ pub struct MnistImage {
     index: u32,
 }
 
+impl MnistImage {
+    // Layout expected by the convolution layers: [channels, height, width].
+    pub fn shape(&self) -> Vec<usize> {
+        return vec![1, self.dimensions.height, self.dimensions.width];
+    }
+
+    pub fn pixels(&self) -> Vec<f64> {
+        return to_f64(self.pixels.clone());
+    }
+
+    pub fn label(&self) -> u8 {
+        return self.label;
+    }
+}
+
+// Raw pixels as the sample and the one-hot encoded label as the target.
+impl Dataset for Vec<MnistImage> {
+    fn sample_count(&self) -> usize {
+        return self.len();
+    }
+
+    fn sample(&self, index: usize) -> (Vec<f64>, Vec<f64>) {
+        return (self[index].pixels(), one_hot_encoding(self[index].label));
+    }
+}
+
+// A small CNN for digit images of `shape` ([1, 28, 28]): a 5x5 convolution to 4
+// channels, ReLU, 2x2 max pooling and a dense layer to 10 logits, to be trained
+// with `Loss::CrossEntropy`. Pixels are expected scaled to [0, 1].
+pub fn mnist_cnn(shape: Vec<usize>) -> Sequential {
+    let mut model = Sequential::new(shape.clone());
+    model.push(Conv2d::new(&model.outputs(), shape, 4, 5));
+    model.push(ActivationLayer::new(
+        &model.outputs(),
+        model.output_shape(),
+        Activation::ReLU,
+    ));
+    model.push(Pool::max_2d(&model.outputs(), model.output_shape(), 2, 2));
+    model.push(Reshape::flatten(&model.outputs(), model.output_shape()));
+    model.push(Layer::with_activation(
+        10,
+        &model.outputs(),
+        Activation::Identity,
+    ));
+    return model;
+}
+
 pub fn read_mnist_labels() -> Vec<MnistImage> {
     let mut label_file_path = FOLDER_PREFIX.clone().to_owned();
     label_file_path.push_str("train-labels-idx1-ubyte");
@@ -123,6 +183,83 @@ fn one_hot_encoding(label: u8) -> Vec<f64> {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::engine::randomize;
+
+    #[test]
+    fn test_mnist_dataset() {
+        let images: Vec<MnistImage> = (0..3)
+            .map(|index| MnistImage {
+                dimensions: ImageDimensions {
+                    height: 2,
+                    width: 2,
+                },
+                label: index as u8 + 4,
+                pixels: vec![index as u8, 0, 255, 1],
+                index,
+            })
+            .collect();
+        assert_eq!(images.sample_count(), 3);
+        let (sample, target) = images.sample(2);
+        assert_eq!(sample, vec![2.0, 0.0, 255.0, 1.0]);
+        assert_eq!(target[6], 1.0);
+        assert_eq!(target.iter().sum::<f64>(), 1.0);
+
+        let loader = DataLoader::new(&images, 2, &seeded_rng(1));
+        let sizes: Vec<usize> = loader.batches().map(|batch| batch.len()).collect();
+        assert_eq!(sizes, vec![2, 1]);
+    }
+
+    #[test]
+    fn test_mnist_cnn() {
+        // vertical bars are labelled 1, horizontal bars 0, at a few offsets
+        let mut images: Vec<MnistImage> = vec![];
+        for offset in [6, 13, 20] {
+            for label in [0u8, 1] {
+                let mut pixels = vec![0u8; 28 * 28];
+                for index in 4..24 {
+                    let (row, column) = match label {
+                        1 => (index, offset),
+                        _ => (offset, index),
+                    };
+                    pixels[row * 28 + column] = 255;
+                }
+                images.push(MnistImage {
+                    dimensions: ImageDimensions {
+                        height: 28,
+                        width: 28,
+                    },
+                    label,
+                    pixels,
+                    index: images.len() as u32,
+                });
+            }
+        }
+
+        let model = mnist_cnn(images[0].shape());
+        assert_eq!(model.output_shape(), vec![10]);
+        randomize(&model.parameters(), 0.1, 5);
+        let loss_graph = Loss::CrossEntropy.build(&model.outputs());
+        let inputs = model.inputs();
+        let loader = DataLoader::new(&images, 3, &seeded_rng(5));
+        let mut losses = vec![];
+        for _ in 0..15 {
+            let mut total = 0.0;
+            for batch in loader.batches() {
+                model.zero_grad();
+                total += accumulate_gradients(batch.len(), |index| {
+                    for (node, pixel) in inputs.iter().zip(batch.samples[index].iter()) {
+                        node.borrow_mut().value = pixel / 255.0;
+                    }
+                    loss_graph.set_targets(&batch.targets[index]);
+                    return loss_graph.loss();
+                });
+                model.update(0.1);
+            }
+            losses.push(total);
+        }
+        assert!(losses[14] < 0.1 * losses[0], "{:?}", losses);
+    }
+
     #[test]
     fn test_read_mnist_labels() {
         let images = read_mnist_labels();
@@ -134,31 +271,24 @@ mod test {
             dimension.width * dimension.height,
         );
         let alpha = 0.01;
-        let outputs = mlp.outputs();
-
-        for image_index in 0..60000 {
-            mlp.set(to_f64(images[image_index].pixels.clone()));
-            let y_hat = one_hot_encoding(images[image_index].label);
-
-            let mut loss = Value::from(0.0);
-            for (index, output) in outputs.iter().enumerate() {
-                let tmp = Engine::pow(&Engine::add(
-                    output,
-                    &Engine::inv(&Value::from(y_hat[index])),
-                ));
-
-                loss = Engine::add(&loss, &tmp);
-            }
-            loss = Engine::mul(&loss, &Value::from(1.0 / y_hat.len() as f64));
-            println!("{} {}", image_index, loss.borrow().value);
-            loss.borrow_mut().forward();
-            loss.borrow_mut().grad = 1.0;
+        // built once, per sample only the pixels and the targets are written
+        let loss_graph = Loss::MeanSquared.build(&mlp.outputs());
 
+        let rng = seeded_rng(0);
+        let (train, validation) = train_validation_split(&images, 0.1, &rng);
+        assert_eq!(validation.sample_count(), 6000);
+        let loader = DataLoader::new(&train, 32, &rng);
+        for (batch_index, batch) in loader.batches().enumerate() {
             mlp.zero_grad();
-            loss.borrow_mut().grad = 1.0;
-            loss.borrow_mut().backward();
+            let loss = accumulate_gradients(batch.len(), |index| {
+                mlp.set(batch.samples[index].clone());
+                loss_graph.set_targets(&batch.targets[index]);
+                return loss_graph.loss();
+            });
+            println!("{} {}", batch_index, loss);
+            // raw pixels reach 255, keep single steps from blowing up the weights
+            clip_grad_norm(&mlp.parameters(), 1.0);
             mlp.update(alpha);
-            //loss.borrow_mut().forward();
         }
     }
 }
@@ -0,0 +1,144 @@
+use crate::engine::Engine;
+use crate::engine::ValueRef;
+use crate::nn::Module;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Activation {
+    Identity,
+    ReLU,
+    LeakyReLU(f64),
+    ELU,
+    GELU,
+    SiLU,
+    Tanh,
+    Sigmoid,
+    Softplus,
+    // normalizes across all outputs of a layer, only valid for whole layers
+    Softmax,
+}
+
+impl Activation {
+    // Mapping of the former `linear` flag.
+    pub fn from_linear(linear: bool) -> Activation {
+        return match linear {
+            true => Activation::Identity,
+            false => Activation::ReLU,
+        };
+    }
+
+    pub fn is_elementwise(&self) -> bool {
+        return *self != Activation::Softmax;
+    }
+
+    pub fn apply(&self, node: &ValueRef) -> ValueRef {
+        return match self {
+            Activation::Identity => node.clone(),
+            Activation::ReLU => Engine::relu(node),
+            Activation::LeakyReLU(slope) => Engine::leaky_relu(node, *slope),
+            Activation::ELU => Engine::elu(node, 1.0),
+            Activation::GELU => Engine::gelu(node),
+            Activation::SiLU => Engine::silu(node),
+            Activation::Tanh => Engine::tanh(node),
+            Activation::Sigmoid => Engine::sigmoid(node),
+            Activation::Softplus => Engine::softplus(node),
+            Activation::Softmax => panic!("softmax needs all outputs of a layer"),
+        };
+    }
+
+    pub fn apply_layer(&self, nodes: &[ValueRef]) -> Vec<ValueRef> {
+        if *self == Activation::Softmax {
+            return Engine::softmax(nodes);
+        }
+        return nodes.iter().map(|node| self.apply(node)).collect();
+    }
+}
+
+// Applies an activation on its own, e.g. after a normalization layer.
+pub struct ActivationLayer {
+    inputs: Vec<ValueRef>,
+    outputs: Vec<ValueRef>,
+    shape: Vec<usize>,
+}
+
+impl ActivationLayer {
+    pub fn new(inputs: &[ValueRef], shape: Vec<usize>, activation: Activation) -> ActivationLayer {
+        assert_eq!(inputs.len(), shape.iter().product::<usize>());
+        return ActivationLayer {
+            inputs: inputs.to_vec(),
+            outputs: activation.apply_layer(inputs),
+            shape,
+        };
+    }
+}
+
+impl Module for ActivationLayer {
+    fn inputs(&self) -> Vec<ValueRef> {
+        return self.inputs.clone();
+    }
+
+    fn outputs(&self) -> Vec<ValueRef> {
+        return self.outputs.clone();
+    }
+
+    fn parameters(&self) -> Vec<ValueRef> {
+        return vec![];
+    }
+
+    fn input_shape(&self) -> Vec<usize> {
+        return self.shape.clone();
+    }
+
+    fn output_shape(&self) -> Vec<usize> {
+        return self.shape.clone();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::engine::Value;
+
+    #[test]
+    fn test_activation_gradients() {
+        let activations = [
+            Activation::ReLU,
+            Activation::LeakyReLU(0.1),
+            Activation::ELU,
+            Activation::GELU,
+            Activation::SiLU,
+            Activation::Tanh,
+            Activation::Sigmoid,
+            Activation::Softplus,
+        ];
+        let epsilon = 1e-6;
+        for activation in activations.iter() {
+            for x in [-1.5, -0.3, 0.4, 2.0] {
+                let input = Value::from(x);
+                input.borrow_mut().needs_grad = true;
+                let output = activation.apply(&input);
+                output.borrow_mut().grad = 1.0;
+                output.borrow_mut().backward();
+
+                let plus = activation.apply(&Value::from(x + epsilon)).borrow().value;
+                let minus = activation.apply(&Value::from(x - epsilon)).borrow().value;
+                let numeric = (plus - minus) / (2.0 * epsilon);
+                assert!(
+                    (input.borrow().grad - numeric).abs() < 1e-5,
+                    "{:?} at {}",
+                    activation,
+                    x
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_softmax() {
+        let inputs: Vec<ValueRef> = [1.0, 2.0, 1000.0].iter().map(|x| Value::from(*x)).collect();
+        let outputs = Activation::Softmax.apply_layer(&inputs);
+        let total: f64 = outputs.iter().map(|output| output.borrow().value).sum();
+        assert!((total - 1.0).abs() < 1e-12);
+        assert!((outputs[2].borrow().value - 1.0).abs() < 1e-12);
+        assert_eq!(Activation::from_linear(false), Activation::ReLU);
+    }
+}
@@ -0,0 +1,197 @@
+use crate::engine::Engine;
+use crate::engine::Value;
+use crate::engine::ValueRef;
+use crate::nn::Linear;
+use crate::nn::Module;
+
+// Single head attention over vectors given per position. With `causal` set a
+// position only attends to itself and earlier positions.
+pub fn scaled_dot_product_attention(
+    queries: &[Vec<ValueRef>],
+    keys: &[Vec<ValueRef>],
+    values: &[Vec<ValueRef>],
+    causal: bool,
+) -> Vec<Vec<ValueRef>> {
+    assert_eq!(keys.len(), values.len());
+    assert!(!keys.is_empty());
+    let scale = Value::from(1.0 / (queries[0].len() as f64).sqrt());
+
+    let mut outputs: Vec<Vec<ValueRef>> = Vec::with_capacity(queries.len());
+    for (position, query) in queries.iter().enumerate() {
+        let visible = match causal {
+            true => position + 1,
+            false => keys.len(),
+        };
+        let scores: Vec<ValueRef> = keys[..visible]
+            .iter()
+            .map(|key| {
+                let products: Vec<ValueRef> = query
+                    .iter()
+                    .zip(key.iter())
+                    .map(|(q, k)| Engine::mul(q, k))
+                    .collect();
+                Engine::mul(&Engine::sum(&products), &scale)
+            })
+            .collect();
+        let weights = Engine::softmax(&scores);
+
+        let output: Vec<ValueRef> = (0..values[0].len())
+            .map(|column| {
+                let weighted: Vec<ValueRef> = weights
+                    .iter()
+                    .zip(values[..visible].iter())
+                    .map(|(weight, value)| Engine::mul(weight, &value[column]))
+                    .collect();
+                Engine::sum(&weighted)
+            })
+            .collect();
+        outputs.push(output);
+    }
+    return outputs;
+}
+
+// Multi-head self-attention over inputs of shape `[sequence_length, model_size]`,
+// every head works on `model_size / heads` dimensions of the projected vectors.
+pub struct MultiHeadAttention {
+    inputs: Vec<ValueRef>,
+    outputs: Vec<ValueRef>,
+    query: Linear,
+    key: Linear,
+    value: Linear,
+    output: Linear,
+    shape: Vec<usize>,
+}
+
+impl MultiHeadAttention {
+    pub fn new(
+        inputs: &[ValueRef],
+        sequence_length: usize,
+        heads: usize,
+        causal: bool,
+    ) -> MultiHeadAttention {
+        assert!(sequence_length > 0 && heads > 0);
+        assert_eq!(inputs.len() % sequence_length, 0);
+        let model_size = inputs.len() / sequence_length;
+        assert_eq!(
+            model_size % heads,
+            0,
+            "model size must be divisible by heads"
+        );
+        let head_size = model_size / heads;
+
+        let query = Linear::new(model_size, model_size);
+        let key = Linear::new(model_size, model_size);
+        let value = Linear::new(model_size, model_size);
+        let output = Linear::new(model_size, model_size);
+
+        let positions: Vec<&[ValueRef]> = inputs.chunks(model_size).collect();
+        let queries: Vec<Vec<ValueRef>> = positions.iter().map(|p| query.apply(p)).collect();
+        let keys: Vec<Vec<ValueRef>> = positions.iter().map(|p| key.apply(p)).collect();
+        let values: Vec<Vec<ValueRef>> = positions.iter().map(|p| value.apply(p)).collect();
+
+        let mut attended: Vec<Vec<ValueRef>> = vec![vec![]; sequence_length];
+        for head in 0..heads {
+            let columns = head * head_size..(head + 1) * head_size;
+            let slice = |vectors: &Vec<Vec<ValueRef>>| -> Vec<Vec<ValueRef>> {
+                return vectors
+                    .iter()
+                    .map(|v| v[columns.clone()].to_vec())
+                    .collect();
+            };
+            let head_outputs = scaled_dot_product_attention(
+                &slice(&queries),
+                &slice(&keys),
+                &slice(&values),
+                causal,
+            );
+            for (position, head_output) in head_outputs.into_iter().enumerate() {
+                attended[position].extend(head_output);
+            }
+        }
+        let outputs: Vec<ValueRef> = attended.iter().flat_map(|a| output.apply(a)).collect();
+
+        return MultiHeadAttention {
+            inputs: inputs.to_vec(),
+            outputs,
+            query,
+            key,
+            value,
+            output,
+            shape: vec![sequence_length, model_size],
+        };
+    }
+}
+
+impl Module for MultiHeadAttention {
+    fn inputs(&self) -> Vec<ValueRef> {
+        return self.inputs.clone();
+    }
+
+    fn outputs(&self) -> Vec<ValueRef> {
+        return self.outputs.clone();
+    }
+
+    fn parameters(&self) -> Vec<ValueRef> {
+        return self
+            .named_parameters()
+            .into_iter()
+            .map(|(_, parameter)| parameter)
+            .collect();
+    }
+
+    fn named_parameters(&self) -> Vec<(String, ValueRef)> {
+        let mut named = self.query.named_parameters("query");
+        named.extend(self.key.named_parameters("key"));
+        named.extend(self.value.named_parameters("value"));
+        named.extend(self.output.named_parameters("output"));
+        return named;
+    }
+
+    fn input_shape(&self) -> Vec<usize> {
+        return self.shape.clone();
+    }
+
+    fn output_shape(&self) -> Vec<usize> {
+        return self.shape.clone();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_causal_attention() {
+        let inputs: Vec<ValueRef> = (0..12).map(|_x| Value::from(0.0)).collect();
+        for input in inputs.iter() {
+            input.borrow_mut().needs_grad = true;
+        }
+        let mut attention = MultiHeadAttention::new(&inputs, 3, 2, true);
+        assert_eq!(attention.output_shape(), vec![3, 4]);
+        assert_eq!(attention.parameter_count(), 4 * 4 * 5);
+
+        let values: Vec<f64> = (0..12).map(|x| (x as f64 * 0.37).cos()).collect();
+        attention.forward(&values);
+        // the first position only sees itself
+        let first = Engine::sum(&attention.outputs()[..4]);
+        first.borrow_mut().forward();
+        first.borrow_mut().grad = 1.0;
+        first.borrow_mut().backward();
+        assert!(inputs[..4].iter().any(|input| input.borrow().grad != 0.0));
+        assert!(inputs[4..].iter().all(|input| input.borrow().grad == 0.0));
+    }
+
+    #[test]
+    fn test_attention_weights_average_values() {
+        let vector = |values: &[f64]| -> Vec<ValueRef> {
+            return values.iter().map(|v| Value::from(*v)).collect();
+        };
+        let queries = vec![vector(&[0.0, 0.0])];
+        let keys = vec![vector(&[1.0, 0.0]), vector(&[0.0, 1.0])];
+        let values = vec![vector(&[2.0, 4.0]), vector(&[6.0, 8.0])];
+        // equal scores, so the output is the mean of the values
+        let outputs = scaled_dot_product_attention(&queries, &keys, &values, false);
+        assert_eq!(outputs[0][0].borrow().value, 4.0);
+        assert_eq!(outputs[0][1].borrow().value, 6.0);
+    }
+}
@@ -0,0 +1,404 @@
+use crate::engine::Engine;
+use crate::engine::Value;
+use crate::engine::ValueRef;
+use crate::nn::Module;
+
+fn output_size(size: usize, kernel_size: usize, stride: usize, padding: usize) -> usize {
+    assert!(stride > 0);
+    assert!(
+        size + 2 * padding >= kernel_size,
+        "kernel does not fit into the padded input"
+    );
+    return (size + 2 * padding - kernel_size) / stride + 1;
+}
+
+fn random_parameters(size: usize) -> Vec<ValueRef> {
+    let parameters: Vec<ValueRef> = (0..size).map(|_x| Value::random()).collect();
+    for parameter in parameters.iter() {
+        parameter.borrow_mut().needs_grad = true;
+    }
+    return parameters;
+}
+
+// 1-D convolution over inputs of shape `[channels, length]`, producing
+// `[out_channels, out_length]`. With a dilation `d` the kernel taps are `d` apart.
+pub struct Conv1d {
+    inputs: Vec<ValueRef>,
+    outputs: Vec<ValueRef>,
+    // laid out as [out_channels, in_channels, kernel_size]
+    weights: Vec<ValueRef>,
+    biases: Vec<ValueRef>,
+    input_shape: Vec<usize>,
+    output_shape: Vec<usize>,
+    kernel_size: usize,
+}
+
+impl Conv1d {
+    pub fn new(
+        inputs: &[ValueRef],
+        input_shape: Vec<usize>,
+        out_channels: usize,
+        kernel_size: usize,
+    ) -> Conv1d {
+        return Conv1d::with_config(inputs, input_shape, out_channels, kernel_size, 1, 0, 1);
+    }
+
+    pub fn with_config(
+        inputs: &[ValueRef],
+        input_shape: Vec<usize>,
+        out_channels: usize,
+        kernel_size: usize,
+        stride: usize,
+        padding: usize,
+        dilation: usize,
+    ) -> Conv1d {
+        assert_eq!(input_shape.len(), 2, "expected [channels, length]");
+        assert_eq!(inputs.len(), input_shape.iter().product::<usize>());
+        assert!(dilation > 0);
+        let (channels, length) = (input_shape[0], input_shape[1]);
+        let span = dilation * (kernel_size - 1) + 1;
+        let out_length = output_size(length, span, stride, padding);
+
+        let weights = random_parameters(out_channels * channels * kernel_size);
+        let biases = random_parameters(out_channels);
+
+        let mut outputs: Vec<ValueRef> = Vec::with_capacity(out_channels * out_length);
+        for out_channel in 0..out_channels {
+            for position in 0..out_length {
+                let mut output = biases[out_channel].clone();
+                for channel in 0..channels {
+                    for tap in 0..kernel_size {
+                        let x = (position * stride + tap * dilation) as isize - padding as isize;
+                        if x < 0 || x >= length as isize {
+                            continue;
+                        }
+                        let weight = (out_channel * channels + channel) * kernel_size + tap;
+                        let input = channel * length + x as usize;
+                        output =
+                            Engine::add(&output, &Engine::mul(&weights[weight], &inputs[input]));
+                    }
+                }
+                outputs.push(output);
+            }
+        }
+
+        return Conv1d {
+            inputs: inputs.to_vec(),
+            outputs,
+            weights,
+            biases,
+            input_shape,
+            output_shape: vec![out_channels, out_length],
+            kernel_size,
+        };
+    }
+}
+
+impl Module for Conv1d {
+    fn inputs(&self) -> Vec<ValueRef> {
+        return self.inputs.clone();
+    }
+
+    fn outputs(&self) -> Vec<ValueRef> {
+        return self.outputs.clone();
+    }
+
+    fn parameters(&self) -> Vec<ValueRef> {
+        return self
+            .weights
+            .iter()
+            .chain(self.biases.iter())
+            .cloned()
+            .collect();
+    }
+
+    fn named_parameters(&self) -> Vec<(String, ValueRef)> {
+        let channels = self.input_shape[0];
+        let kernel = self.kernel_size;
+        let mut named: Vec<(String, ValueRef)> = Vec::with_capacity(self.parameter_count());
+        for (index, weight) in self.weights.iter().enumerate() {
+            let name = format!(
+                "weight.{}.{}.{}",
+                index / (channels * kernel),
+                index / kernel % channels,
+                index % kernel
+            );
+            named.push((name, weight.clone()));
+        }
+        for (index, bias) in self.biases.iter().enumerate() {
+            named.push((format!("bias.{}", index), bias.clone()));
+        }
+        return named;
+    }
+
+    fn input_shape(&self) -> Vec<usize> {
+        return self.input_shape.clone();
+    }
+
+    fn output_shape(&self) -> Vec<usize> {
+        return self.output_shape.clone();
+    }
+}
+
+// 2-D convolution over inputs of shape `[channels, height, width]`, producing
+// `[out_channels, out_height, out_width]`. Padded positions are zero and simply
+// left out of the sums.
+pub struct Conv2d {
+    inputs: Vec<ValueRef>,
+    outputs: Vec<ValueRef>,
+    // laid out as [out_channels, in_channels, kernel_size, kernel_size]
+    weights: Vec<ValueRef>,
+    biases: Vec<ValueRef>,
+    input_shape: Vec<usize>,
+    output_shape: Vec<usize>,
+    kernel_size: usize,
+}
+
+impl Conv2d {
+    pub fn new(
+        inputs: &[ValueRef],
+        input_shape: Vec<usize>,
+        out_channels: usize,
+        kernel_size: usize,
+    ) -> Conv2d {
+        return Conv2d::with_config(inputs, input_shape, out_channels, kernel_size, 1, 0);
+    }
+
+    pub fn with_config(
+        inputs: &[ValueRef],
+        input_shape: Vec<usize>,
+        out_channels: usize,
+        kernel_size: usize,
+        stride: usize,
+        padding: usize,
+    ) -> Conv2d {
+        assert_eq!(input_shape.len(), 3, "expected [channels, height, width]");
+        assert_eq!(inputs.len(), input_shape.iter().product::<usize>());
+        let (channels, height, width) = (input_shape[0], input_shape[1], input_shape[2]);
+        let out_height = output_size(height, kernel_size, stride, padding);
+        let out_width = output_size(width, kernel_size, stride, padding);
+
+        let weights = random_parameters(out_channels * channels * kernel_size * kernel_size);
+        let biases = random_parameters(out_channels);
+
+        let mut outputs: Vec<ValueRef> = Vec::with_capacity(out_channels * out_height * out_width);
+        for out_channel in 0..out_channels {
+            for row in 0..out_height {
+                for column in 0..out_width {
+                    let mut output = biases[out_channel].clone();
+                    for channel in 0..channels {
+                        for kernel_row in 0..kernel_size {
+                            let y = (row * stride + kernel_row) as isize - padding as isize;
+                            if y < 0 || y >= height as isize {
+                                continue;
+                            }
+                            for kernel_column in 0..kernel_size {
+                                let x =
+                                    (column * stride + kernel_column) as isize - padding as isize;
+                                if x < 0 || x >= width as isize {
+                                    continue;
+                                }
+                                let weight = ((out_channel * channels + channel) * kernel_size
+                                    + kernel_row)
+                                    * kernel_size
+                                    + kernel_column;
+                                let input = (channel * height + y as usize) * width + x as usize;
+                                output = Engine::add(
+                                    &output,
+                                    &Engine::mul(&weights[weight], &inputs[input]),
+                                );
+                            }
+                        }
+                    }
+                    outputs.push(output);
+                }
+            }
+        }
+
+        return Conv2d {
+            inputs: inputs.to_vec(),
+            outputs,
+            weights,
+            biases,
+            input_shape,
+            output_shape: vec![out_channels, out_height, out_width],
+            kernel_size,
+        };
+    }
+}
+
+impl Module for Conv2d {
+    fn inputs(&self) -> Vec<ValueRef> {
+        return self.inputs.clone();
+    }
+
+    fn outputs(&self) -> Vec<ValueRef> {
+        return self.outputs.clone();
+    }
+
+    fn parameters(&self) -> Vec<ValueRef> {
+        return self
+            .weights
+            .iter()
+            .chain(self.biases.iter())
+            .cloned()
+            .collect();
+    }
+
+    fn named_parameters(&self) -> Vec<(String, ValueRef)> {
+        let channels = self.input_shape[0];
+        let kernel = self.kernel_size;
+        let mut named: Vec<(String, ValueRef)> = Vec::with_capacity(self.parameter_count());
+        for (index, weight) in self.weights.iter().enumerate() {
+            let name = format!(
+                "weight.{}.{}.{}.{}",
+                index / (channels * kernel * kernel),
+                index / (kernel * kernel) % channels,
+                index / kernel % kernel,
+                index % kernel
+            );
+            named.push((name, weight.clone()));
+        }
+        for (index, bias) in self.biases.iter().enumerate() {
+            named.push((format!("bias.{}", index), bias.clone()));
+        }
+        return named;
+    }
+
+    fn input_shape(&self) -> Vec<usize> {
+        return self.input_shape.clone();
+    }
+
+    fn output_shape(&self) -> Vec<usize> {
+        return self.output_shape.clone();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::nn::Activation;
+    use crate::nn::ActivationLayer;
+    use crate::nn::Layer;
+    use crate::nn::Pool;
+    use crate::nn::Reshape;
+    use crate::nn::Sequential;
+
+    // Compares the gradient of sum(outputs^2) with central differences for every parameter.
+    fn check_gradients(module: &mut dyn Module, inputs: &[f64]) {
+        let squares: Vec<ValueRef> = module.outputs().iter().map(Engine::pow).collect();
+        let loss = Engine::sum(&squares);
+        module.forward(inputs);
+        loss.borrow_mut().forward();
+        module.zero_grad();
+        loss.borrow_mut().grad = 1.0;
+        loss.borrow_mut().backward();
+
+        let epsilon = 1e-6;
+        for parameter in module.parameters().iter() {
+            let value = parameter.borrow().value;
+            parameter.borrow_mut().value = value + epsilon;
+            loss.borrow_mut().forward();
+            let plus = loss.borrow().value;
+            parameter.borrow_mut().value = value - epsilon;
+            loss.borrow_mut().forward();
+            let minus = loss.borrow().value;
+            parameter.borrow_mut().value = value;
+
+            let numeric = (plus - minus) / (2.0 * epsilon);
+            let analytic = parameter.borrow().grad;
+            assert!((analytic - numeric).abs() < 1e-4 * (1.0 + numeric.abs()));
+        }
+    }
+
+    #[test]
+    fn test_conv2d_gradients() {
+        let shape = vec![2, 4, 5];
+        let inputs: Vec<ValueRef> = (0..40).map(|_x| Value::from(0.0)).collect();
+        let mut conv = Conv2d::with_config(&inputs, shape, 3, 3, 2, 1);
+        assert_eq!(conv.output_shape(), vec![3, 2, 3]);
+        assert_eq!(conv.parameter_count(), 3 * 2 * 9 + 3);
+        assert_eq!(conv.named_parameters()[9 * 2 + 4].0, "weight.1.0.1.1");
+
+        let values: Vec<f64> = (0..40)
+            .map(|x| ((x * 7) % 11) as f64 / 11.0 - 0.5)
+            .collect();
+        check_gradients(&mut conv, &values);
+    }
+
+    #[test]
+    fn test_conv1d_gradients() {
+        let shape = vec![2, 9];
+        let inputs: Vec<ValueRef> = (0..18).map(|_x| Value::from(0.0)).collect();
+        let mut conv = Conv1d::with_config(&inputs, shape, 2, 3, 2, 1, 2);
+        // (9 + 2 - 2 * (3 - 1) - 1) / 2 + 1
+        assert_eq!(conv.output_shape(), vec![2, 4]);
+        assert_eq!(conv.named_parameters()[3 + 2].0, "weight.0.1.2");
+
+        let values: Vec<f64> = (0..18).map(|x| ((x * 5) % 7) as f64 / 7.0 - 0.5).collect();
+        check_gradients(&mut conv, &values);
+
+        let mut model = Sequential::new(vec![1, 12]);
+        model.push(Conv1d::with_config(
+            &model.outputs(),
+            vec![1, 12],
+            3,
+            3,
+            1,
+            2,
+            3,
+        ));
+        model.push(Pool::max_1d(&model.outputs(), vec![3, 10], 2, 2));
+        model.push(Pool::avg_1d(&model.outputs(), vec![3, 5], 3, 1));
+        assert_eq!(model.output_shape(), vec![3, 3]);
+        let signal: Vec<f64> = (0..12).map(|x| (x as f64 * 0.7).sin()).collect();
+        check_gradients(&mut model, &signal);
+    }
+
+    #[test]
+    fn test_small_cnn() {
+        // tells apart vertical and horizontal bars on a 6x6 image
+        let mut model = Sequential::new(vec![1, 6, 6]);
+        model.push(Conv2d::new(&model.outputs(), vec![1, 6, 6], 2, 3));
+        model.push(ActivationLayer::new(
+            &model.outputs(),
+            vec![2, 4, 4],
+            Activation::Tanh,
+        ));
+        model.push(Pool::max_2d(&model.outputs(), vec![2, 4, 4], 2, 2));
+        model.push(Pool::avg_2d(&model.outputs(), vec![2, 2, 2], 2, 2));
+        model.push(Reshape::flatten(&model.outputs(), vec![2, 1, 1]));
+        model.push(Layer::with_activation(
+            1,
+            &model.outputs(),
+            Activation::Sigmoid,
+        ));
+
+        let mut vertical = vec![0.0; 36];
+        let mut horizontal = vec![0.0; 36];
+        for index in 0..6 {
+            vertical[index * 6 + 2] = 1.0;
+            horizontal[2 * 6 + index] = 1.0;
+        }
+        for (index, parameter) in model.parameters().iter().enumerate() {
+            parameter.borrow_mut().value = ((index * 5) % 7) as f64 / 7.0 - 0.5;
+        }
+
+        let output = model.outputs()[0].clone();
+        let target = Value::from(0.0);
+        let loss = Engine::pow(&Engine::add(&output, &Engine::inv(&target)));
+        for _ in 0..300 {
+            for (image, label) in [(&vertical, 1.0), (&horizontal, 0.0)] {
+                model.forward(image);
+                target.borrow_mut().value = label;
+                loss.borrow_mut().forward();
+                model.zero_grad();
+                loss.borrow_mut().grad = 1.0;
+                loss.borrow_mut().backward();
+                model.update(0.5);
+            }
+        }
+        assert!(model.forward(&vertical)[0] > 0.8);
+        assert!(model.forward(&horizontal)[0] < 0.2);
+    }
+}
@@ -0,0 +1,126 @@
+use rand::Rng;
+
+use crate::engine::Engine;
+use crate::engine::RngRef;
+use crate::engine::Value;
+use crate::engine::ValueRef;
+use crate::nn::Module;
+
+// Zeroes each input with probability `p` and scales the kept ones by 1 / (1 - p).
+// The mask is a set of constant nodes multiplied into the graph, resampled from the
+// model's random stream before every forward pass in training mode and set to one
+// in eval mode.
+pub struct Dropout {
+    inputs: Vec<ValueRef>,
+    masks: Vec<ValueRef>,
+    outputs: Vec<ValueRef>,
+    shape: Vec<usize>,
+    p: f64,
+    training: bool,
+    rng: RngRef,
+}
+
+impl Dropout {
+    pub fn new(inputs: &[ValueRef], shape: Vec<usize>, p: f64, rng: &RngRef) -> Dropout {
+        assert!((0.0..1.0).contains(&p));
+        assert_eq!(inputs.len(), shape.iter().product::<usize>());
+        let masks: Vec<ValueRef> = inputs.iter().map(|_x| Value::from(1.0)).collect();
+        let outputs: Vec<ValueRef> = inputs
+            .iter()
+            .zip(masks.iter())
+            .map(|(input, mask)| Engine::mul(input, mask))
+            .collect();
+
+        let mut dropout = Dropout {
+            inputs: inputs.to_vec(),
+            masks,
+            outputs,
+            shape,
+            p,
+            training: true,
+            rng: rng.clone(),
+        };
+        dropout.before_forward();
+        return dropout;
+    }
+
+    pub fn masks(&self) -> Vec<f64> {
+        return self.masks.iter().map(|mask| mask.borrow().value).collect();
+    }
+}
+
+impl Module for Dropout {
+    fn inputs(&self) -> Vec<ValueRef> {
+        return self.inputs.clone();
+    }
+
+    fn outputs(&self) -> Vec<ValueRef> {
+        return self.outputs.clone();
+    }
+
+    fn parameters(&self) -> Vec<ValueRef> {
+        return vec![];
+    }
+
+    fn input_shape(&self) -> Vec<usize> {
+        return self.shape.clone();
+    }
+
+    fn output_shape(&self) -> Vec<usize> {
+        return self.shape.clone();
+    }
+
+    fn before_forward(&mut self) {
+        let scale = 1.0 / (1.0 - self.p);
+        let mut rng = self.rng.borrow_mut();
+        for mask in self.masks.iter() {
+            mask.borrow_mut().value = match self.training {
+                true if rng.gen::<f64>() < self.p => 0.0,
+                true => scale,
+                false => 1.0,
+            };
+        }
+    }
+
+    fn train(&mut self, training: bool) {
+        self.training = training;
+        self.before_forward();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::engine::seeded_rng;
+
+    #[test]
+    fn test_dropout() {
+        let inputs: Vec<ValueRef> = (0..1000).map(|_x| Value::from(2.0)).collect();
+        for input in inputs.iter() {
+            input.borrow_mut().needs_grad = true;
+        }
+        let mut dropout = Dropout::new(&inputs, vec![1000], 0.25, &seeded_rng(7));
+
+        let values: Vec<f64> = inputs.iter().map(|input| input.borrow().value).collect();
+        let outputs = dropout.forward(&values);
+        let dropped = outputs.iter().filter(|output| **output == 0.0).count();
+        assert!(dropped > 200 && dropped < 300);
+        for output in outputs.iter() {
+            assert!(*output == 0.0 || (*output - 2.0 / 0.75).abs() < 1e-12);
+        }
+
+        let loss = Engine::sum(&dropout.outputs());
+        loss.borrow_mut().grad = 1.0;
+        loss.borrow_mut().backward();
+        for (input, mask) in inputs.iter().zip(dropout.masks().iter()) {
+            assert_eq!(input.borrow().grad, *mask);
+        }
+
+        let previous = dropout.masks();
+        dropout.forward(&values);
+        assert_ne!(previous, dropout.masks());
+
+        dropout.eval();
+        assert_eq!(dropout.forward(&values), values);
+    }
+}
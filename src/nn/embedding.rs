@@ -0,0 +1,157 @@
+use crate::engine::Engine;
+use crate::engine::Value;
+use crate::engine::ValueRef;
+use crate::nn::Module;
+
+// Looks up a learnable vector for each of its inputs, which hold integer ids. The
+// outputs have shape `[inputs, dimension]` and only the rows picked in a forward
+// pass receive gradients.
+pub struct Embedding {
+    inputs: Vec<ValueRef>,
+    outputs: Vec<ValueRef>,
+    // laid out as [vocabulary_size, dimension]
+    weights: Vec<ValueRef>,
+    vocabulary_size: usize,
+    dimension: usize,
+}
+
+impl Embedding {
+    pub fn new(inputs: &[ValueRef], vocabulary_size: usize, dimension: usize) -> Embedding {
+        assert!(vocabulary_size > 0 && dimension > 0);
+        let weights: Vec<ValueRef> = (0..vocabulary_size * dimension)
+            .map(|_x| Value::random())
+            .collect();
+        for weight in weights.iter() {
+            weight.borrow_mut().needs_grad = true;
+        }
+
+        let mut outputs: Vec<ValueRef> = Vec::with_capacity(inputs.len() * dimension);
+        for input in inputs.iter() {
+            for column in 0..dimension {
+                let options: Vec<ValueRef> = (0..vocabulary_size)
+                    .map(|row| weights[row * dimension + column].clone())
+                    .collect();
+                outputs.push(Engine::select(input, &options));
+            }
+        }
+
+        return Embedding {
+            inputs: inputs.to_vec(),
+            outputs,
+            weights,
+            vocabulary_size,
+            dimension,
+        };
+    }
+
+    pub fn lookup(&mut self, ids: &[usize]) -> Vec<f64> {
+        let ids: Vec<f64> = ids.iter().map(|id| *id as f64).collect();
+        return self.forward(&ids);
+    }
+
+    pub fn row(&self, id: usize) -> Vec<ValueRef> {
+        assert!(id < self.vocabulary_size);
+        return self.weights[id * self.dimension..(id + 1) * self.dimension].to_vec();
+    }
+}
+
+impl Module for Embedding {
+    fn inputs(&self) -> Vec<ValueRef> {
+        return self.inputs.clone();
+    }
+
+    fn outputs(&self) -> Vec<ValueRef> {
+        return self.outputs.clone();
+    }
+
+    fn parameters(&self) -> Vec<ValueRef> {
+        return self.weights.clone();
+    }
+
+    fn named_parameters(&self) -> Vec<(String, ValueRef)> {
+        return self
+            .weights
+            .iter()
+            .enumerate()
+            .map(|(index, weight)| {
+                let name = format!(
+                    "weight.{}.{}",
+                    index / self.dimension,
+                    index % self.dimension
+                );
+                (name, weight.clone())
+            })
+            .collect();
+    }
+
+    fn output_shape(&self) -> Vec<usize> {
+        return vec![self.inputs.len(), self.dimension];
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::nn::Activation;
+    use crate::nn::Layer;
+    use crate::nn::Reshape;
+    use crate::nn::Sequential;
+
+    #[test]
+    fn test_only_used_rows_get_gradients() {
+        let ids: Vec<ValueRef> = (0..3).map(|_x| Value::from(0.0)).collect();
+        let mut embedding = Embedding::new(&ids, 5, 2);
+        let outputs = embedding.lookup(&[3, 1, 3]);
+        assert_eq!(embedding.output_shape(), vec![3, 2]);
+        assert_eq!(outputs[0], embedding.row(3)[0].borrow().value);
+        assert_eq!(outputs[3], embedding.row(1)[1].borrow().value);
+
+        let total = Engine::sum(&embedding.outputs());
+        embedding.zero_grad();
+        total.borrow_mut().grad = 1.0;
+        total.borrow_mut().backward();
+        for id in 0..5 {
+            let expected = match id {
+                3 => 2.0,
+                1 => 1.0,
+                _ => 0.0,
+            };
+            for weight in embedding.row(id).iter() {
+                assert_eq!(weight.borrow().grad, expected);
+            }
+        }
+        assert_eq!(embedding.named_parameters()[7].0, "weight.3.1");
+    }
+
+    #[test]
+    fn test_categorical_model() {
+        // learns which of six categories belong to the positive class
+        let mut model = Sequential::new(vec![1]);
+        model.push(Embedding::new(&model.outputs(), 6, 3));
+        model.push(Reshape::flatten(&model.outputs(), vec![1, 3]));
+        model.push(Layer::with_activation(
+            1,
+            &model.outputs(),
+            Activation::Sigmoid,
+        ));
+
+        let output = model.outputs()[0].clone();
+        let target = Value::from(0.0);
+        let loss = Engine::pow(&Engine::add(&output, &Engine::inv(&target)));
+        for _ in 0..500 {
+            for id in 0..6 {
+                model.forward(&[id as f64]);
+                target.borrow_mut().value = (id % 3 == 0) as usize as f64;
+                loss.borrow_mut().forward();
+                model.zero_grad();
+                loss.borrow_mut().grad = 1.0;
+                loss.borrow_mut().backward();
+                model.update(0.5);
+            }
+        }
+        for id in 0..6 {
+            let prediction = model.forward(&[id as f64])[0];
+            assert_eq!(prediction > 0.5, id % 3 == 0);
+        }
+    }
+}
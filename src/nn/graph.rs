@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::engine::Engine;
+use crate::engine::Value;
+use crate::engine::ValueRef;
+use crate::nn::Module;
+
+struct Tensor {
+    nodes: Vec<ValueRef>,
+    shape: Vec<usize>,
+}
+
+// Builds models as a DAG of named tensors, which allows skip connections, merging
+// branches and several inputs or outputs.
+//
+// let mut graph = Graph::new();
+// graph.input("x", vec![4]);
+// graph.apply("hidden", "x", |inputs, _| Layer::with_activation(4, inputs, Activation::ReLU));
+// graph.add("residual", "x", "hidden");
+// graph.output("residual");
+pub struct Graph {
+    tensors: HashMap<String, Tensor>,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+    modules: Vec<(String, Box<dyn Module>)>,
+}
+
+impl Graph {
+    pub fn new() -> Graph {
+        return Graph {
+            tensors: HashMap::new(),
+            inputs: vec![],
+            outputs: vec![],
+            modules: vec![],
+        };
+    }
+
+    pub fn input(&mut self, name: &str, shape: Vec<usize>) -> Vec<ValueRef> {
+        let size = shape.iter().product();
+        let nodes: Vec<ValueRef> = (0..size).map(|_x| Value::from(0.0)).collect();
+        self.insert(name, nodes.clone(), shape);
+        self.inputs.push(name.to_string());
+        return nodes;
+    }
+
+    // Builds a module on the tensor `input` and registers its outputs as `output`.
+    pub fn apply<M, F>(&mut self, output: &str, input: &str, build: F) -> Vec<ValueRef>
+    where
+        M: Module + 'static,
+        F: FnOnce(&Vec<ValueRef>, &[usize]) -> M,
+    {
+        let tensor = self.get(input);
+        let module = build(&tensor.nodes, &tensor.shape);
+        let inputs = module.inputs();
+        assert!(
+            tensor.nodes.len() == inputs.len()
+                && tensor
+                    .nodes
+                    .iter()
+                    .zip(inputs.iter())
+                    .all(|(t, i)| Rc::ptr_eq(t, i)),
+            "module {} is not connected to tensor {}",
+            output,
+            input
+        );
+        assert_eq!(
+            tensor.shape,
+            module.input_shape(),
+            "module {} expects a different input shape",
+            output
+        );
+
+        let nodes = module.outputs();
+        self.insert(output, nodes.clone(), module.output_shape());
+        self.modules.push((output.to_string(), Box::new(module)));
+        return nodes;
+    }
+
+    pub fn add(&mut self, output: &str, left: &str, right: &str) -> Vec<ValueRef> {
+        let (left, right) = (self.get(left), self.get(right));
+        assert_eq!(
+            left.shape, right.shape,
+            "cannot add tensors of different shapes"
+        );
+        let nodes: Vec<ValueRef> = left
+            .nodes
+            .iter()
+            .zip(right.nodes.iter())
+            .map(|(l, r)| Engine::add(l, r))
+            .collect();
+        let shape = left.shape.clone();
+        self.insert(output, nodes.clone(), shape);
+        return nodes;
+    }
+
+    // Concatenates along the first axis, the remaining axes have to agree.
+    pub fn concat(&mut self, output: &str, inputs: &[&str]) -> Vec<ValueRef> {
+        assert!(!inputs.is_empty());
+        let mut nodes: Vec<ValueRef> = vec![];
+        let mut shape = self.get(inputs[0]).shape.clone();
+        shape[0] = 0;
+        for input in inputs.iter() {
+            let tensor = self.get(input);
+            assert_eq!(
+                tensor.shape[1..],
+                shape[1..],
+                "cannot concatenate tensor {}",
+                input
+            );
+            shape[0] += tensor.shape[0];
+            nodes.extend(tensor.nodes.iter().cloned());
+        }
+        self.insert(output, nodes.clone(), shape);
+        return nodes;
+    }
+
+    pub fn output(&mut self, name: &str) {
+        self.get(name);
+        self.outputs.push(name.to_string());
+    }
+
+    pub fn tensor(&self, name: &str) -> Vec<ValueRef> {
+        return self.get(name).nodes.clone();
+    }
+
+    pub fn shape(&self, name: &str) -> Vec<usize> {
+        return self.get(name).shape.clone();
+    }
+
+    // Inputs and outputs are given in the order they were declared.
+    pub fn forward_many(&mut self, inputs: &[&[f64]]) -> Vec<Vec<f64>> {
+        assert_eq!(inputs.len(), self.inputs.len());
+        self.before_forward();
+        for (name, values) in self.inputs.iter().zip(inputs.iter()) {
+            let nodes = &self.get(name).nodes;
+            assert_eq!(nodes.len(), values.len(), "wrong size for input {}", name);
+            for (node, value) in nodes.iter().zip(values.iter()) {
+                node.borrow_mut().value = *value;
+            }
+        }
+
+        let nodes: Vec<ValueRef> = self
+            .outputs
+            .iter()
+            .flat_map(|name| self.get(name).nodes.clone())
+            .collect();
+        Value::forward_all(&nodes);
+        let outputs: Vec<Vec<f64>> = self
+            .outputs
+            .iter()
+            .map(|name| {
+                let nodes = &self.get(name).nodes;
+                return nodes.iter().map(|node| node.borrow().value).collect();
+            })
+            .collect();
+        self.after_forward();
+        return outputs;
+    }
+
+    fn get(&self, name: &str) -> &Tensor {
+        return match self.tensors.get(name) {
+            Some(tensor) => tensor,
+            None => panic!("unknown tensor {}", name),
+        };
+    }
+
+    fn insert(&mut self, name: &str, nodes: Vec<ValueRef>, shape: Vec<usize>) {
+        assert!(
+            !self.tensors.contains_key(name),
+            "tensor {} already exists",
+            name
+        );
+        self.tensors
+            .insert(name.to_string(), Tensor { nodes, shape });
+    }
+
+    fn collect(&self, names: &[String]) -> Vec<ValueRef> {
+        let mut nodes: Vec<ValueRef> = vec![];
+        for name in names.iter() {
+            nodes.extend(self.get(name).nodes.iter().cloned());
+        }
+        return nodes;
+    }
+}
+
+impl Default for Graph {
+    fn default() -> Graph {
+        return Graph::new();
+    }
+}
+
+// As a module all inputs and all outputs are flattened into one list each.
+impl Module for Graph {
+    fn inputs(&self) -> Vec<ValueRef> {
+        return self.collect(&self.inputs);
+    }
+
+    fn outputs(&self) -> Vec<ValueRef> {
+        return self.collect(&self.outputs);
+    }
+
+    fn parameters(&self) -> Vec<ValueRef> {
+        let mut parameters: Vec<ValueRef> = vec![];
+        for (_, module) in self.modules.iter() {
+            parameters.extend(module.parameters());
+        }
+        return parameters;
+    }
+
+    fn named_parameters(&self) -> Vec<(String, ValueRef)> {
+        let mut named: Vec<(String, ValueRef)> = vec![];
+        for (name, module) in self.modules.iter() {
+            for (parameter_name, parameter) in module.named_parameters() {
+                named.push((format!("{}.{}", name, parameter_name), parameter));
+            }
+        }
+        return named;
+    }
+
+    fn before_forward(&mut self) {
+        for (_, module) in self.modules.iter_mut() {
+            module.before_forward();
+        }
+    }
+
+    fn after_forward(&mut self) {
+        for (_, module) in self.modules.iter_mut() {
+            module.after_forward();
+        }
+    }
+
+    fn buffers(&self) -> Vec<f64> {
+        return self
+            .modules
+            .iter()
+            .flat_map(|(_, module)| module.buffers())
+            .collect();
+    }
+
+    fn set_buffers(&mut self, buffers: &[f64]) {
+        let mut offset = 0;
+        for (_, module) in self.modules.iter_mut() {
+            let count = module.buffers().len();
+            module.set_buffers(&buffers[offset..offset + count]);
+            offset += count;
+        }
+        assert_eq!(offset, buffers.len());
+    }
+
+    fn train(&mut self, training: bool) {
+        for (_, module) in self.modules.iter_mut() {
+            module.train(training);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::nn::Activation;
+    use crate::nn::Layer;
+
+    #[test]
+    fn test_residual() {
+        let mut graph = Graph::new();
+        graph.input("x", vec![3]);
+        graph.apply("hidden", "x", |inputs, _| {
+            Layer::with_activation(3, inputs, Activation::Tanh)
+        });
+        graph.add("residual", "x", "hidden");
+        graph.output("residual");
+
+        let outputs = graph.forward(&[1.0, 2.0, 3.0]);
+        let hidden: Vec<f64> = graph
+            .tensor("hidden")
+            .iter()
+            .map(|node| node.borrow().value)
+            .collect();
+        for index in 0..3 {
+            assert_eq!(outputs[index], index as f64 + 1.0 + hidden[index]);
+        }
+        assert_eq!(graph.named_parameters()[0].0, "hidden.neurons.0.bias");
+    }
+
+    #[test]
+    fn test_multiple_inputs_and_outputs() {
+        let mut graph = Graph::new();
+        graph.input("a", vec![2]);
+        graph.input("b", vec![3]);
+        graph.concat("ab", &["a", "b"]);
+        graph.apply("left", "ab", |inputs, _| {
+            Layer::with_activation(2, inputs, Activation::ReLU)
+        });
+        graph.apply("right", "ab", |inputs, _| {
+            Layer::with_activation(1, inputs, Activation::Sigmoid)
+        });
+        graph.output("left");
+        graph.output("right");
+
+        assert_eq!(graph.shape("ab"), vec![5]);
+        assert_eq!(graph.inputs().len(), 5);
+        assert_eq!(graph.parameter_count(), 2 * 6 + 6);
+        let outputs = graph.forward_many(&[&[1.0, 0.5], &[0.0, -1.0, 2.0]]);
+        assert_eq!(outputs.len(), 2);
+        assert_eq!(outputs[0].len(), 2);
+        assert!(outputs[1][0] > 0.0 && outputs[1][0] < 1.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot add tensors of different shapes")]
+    fn test_add_shape_mismatch() {
+        let mut graph = Graph::new();
+        graph.input("a", vec![2]);
+        graph.input("b", vec![3]);
+        graph.add("sum", "a", "b");
+    }
+}
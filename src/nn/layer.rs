@@ -1,15 +1,32 @@
 use crate::engine::ValueRef;
+use crate::nn::Activation;
+use crate::nn::Module;
 use crate::nn::Neuron;
 
 pub struct Layer {
     neurons: Vec<Neuron>,
+    inputs: Vec<ValueRef>,
+    activation: Activation,
     pub outputs: Vec<ValueRef>,
 }
 
 impl Layer {
     pub fn new(n_neurons: usize, inputs: &Vec<ValueRef>, linear: bool) -> Layer {
+        return Layer::with_activation(n_neurons, inputs, Activation::from_linear(linear));
+    }
+
+    pub fn with_activation(
+        n_neurons: usize,
+        inputs: &Vec<ValueRef>,
+        activation: Activation,
+    ) -> Layer {
+        // layer wide activations are applied on top of linear neurons
+        let neuron_activation = match activation.is_elementwise() {
+            true => activation,
+            false => Activation::Identity,
+        };
         let neurons: Vec<Neuron> = (0..n_neurons)
-            .map(|_| Neuron::new(&inputs.clone(), linear))
+            .map(|_| Neuron::with_activation(&inputs.clone(), neuron_activation))
             .collect();
         let mut outputs: Vec<ValueRef> = vec![];
         outputs.reserve(n_neurons);
@@ -17,25 +34,57 @@ impl Layer {
         for neuron in neurons.iter() {
             outputs.push(neuron.output.clone());
         }
+        if !activation.is_elementwise() {
+            outputs = activation.apply_layer(&outputs);
+        }
+
+        return Layer {
+            neurons,
+            inputs: inputs.clone(),
+            activation,
+            outputs,
+        };
+    }
 
-        return Layer { neurons, outputs };
+    pub fn activation(&self) -> Activation {
+        return self.activation;
     }
 
-    pub fn zero_grad(&self) {
+    pub fn set(&self, inputs: Vec<f64>) {
         for neuron in self.neurons.iter() {
-            neuron.zero_grad()
+            neuron.set(inputs.clone())
         }
     }
+}
 
-    pub fn update(&self, alpha: f64) {
+impl Module for Layer {
+    fn inputs(&self) -> Vec<ValueRef> {
+        return self.inputs.clone();
+    }
+
+    fn outputs(&self) -> Vec<ValueRef> {
+        return self.outputs.clone();
+    }
+
+    fn parameters(&self) -> Vec<ValueRef> {
+        let mut parameters: Vec<ValueRef> = Vec::with_capacity(self.parameter_count());
         for neuron in self.neurons.iter() {
-            neuron.update(alpha)
+            parameters.extend(neuron.parameters());
         }
+        return parameters;
     }
 
-    pub fn set(&self, inputs: Vec<f64>) {
-        for neuron in self.neurons.iter() {
-            neuron.set(inputs.clone())
+    fn named_parameters(&self) -> Vec<(String, ValueRef)> {
+        let mut named: Vec<(String, ValueRef)> = Vec::with_capacity(self.parameter_count());
+        for (index, neuron) in self.neurons.iter().enumerate() {
+            for (name, parameter) in neuron.named_parameters() {
+                named.push((format!("neurons.{}.{}", index, name), parameter));
+            }
         }
+        return named;
+    }
+
+    fn parameter_count(&self) -> usize {
+        return self.neurons.iter().map(|n| n.parameter_count()).sum();
     }
 }
@@ -0,0 +1,63 @@
+use crate::engine::Engine;
+use crate::engine::Value;
+use crate::engine::ValueRef;
+
+// Affine map `W x + b` that is not bound to particular input nodes, so the same
+// parameters can be applied at several places of a graph (time steps, sequence
+// positions). Parameters are drawn uniformly from [-bound, bound].
+pub struct Linear {
+    // one row per output, [bias, weights...] like the parameters of a `Neuron`
+    rows: Vec<Vec<ValueRef>>,
+}
+
+impl Linear {
+    pub fn new(inputs: usize, outputs: usize) -> Linear {
+        return Linear::with_bound(inputs, outputs, 1.0 / (inputs as f64).sqrt());
+    }
+
+    pub fn with_bound(inputs: usize, outputs: usize, bound: f64) -> Linear {
+        let rows = (0..outputs)
+            .map(|_| {
+                (0..inputs + 1)
+                    .map(|_x| {
+                        let parameter = Value::random();
+                        let uniform = parameter.borrow().value;
+                        parameter.borrow_mut().value = (2.0 * uniform - 1.0) * bound;
+                        parameter.borrow_mut().needs_grad = true;
+                        parameter
+                    })
+                    .collect()
+            })
+            .collect();
+        return Linear { rows };
+    }
+
+    pub fn apply(&self, inputs: &[ValueRef]) -> Vec<ValueRef> {
+        return self
+            .rows
+            .iter()
+            .map(|row| {
+                assert_eq!(row.len(), inputs.len() + 1);
+                let mut output = row[0].clone();
+                for (weight, input) in row[1..].iter().zip(inputs.iter()) {
+                    output = Engine::add(&output, &Engine::mul(weight, input));
+                }
+                output
+            })
+            .collect();
+    }
+
+    pub fn named_parameters(&self, prefix: &str) -> Vec<(String, ValueRef)> {
+        let mut named: Vec<(String, ValueRef)> = vec![];
+        for (row_index, row) in self.rows.iter().enumerate() {
+            for (index, parameter) in row.iter().enumerate() {
+                let name = match index {
+                    0 => format!("{}.{}.bias", prefix, row_index),
+                    _ => format!("{}.{}.weight.{}", prefix, row_index, index - 1),
+                };
+                named.push((name, parameter.clone()));
+            }
+        }
+        return named;
+    }
+}
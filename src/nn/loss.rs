@@ -0,0 +1,141 @@
+use crate::engine::Engine;
+use crate::engine::Value;
+use crate::engine::ValueRef;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Loss {
+    MeanSquared,
+    // Softmax over the outputs followed by the cross entropy against the targets,
+    // which are probabilities, e.g. a one-hot label.
+    CrossEntropy,
+}
+
+impl Loss {
+    pub fn build(&self, outputs: &[ValueRef]) -> LossGraph {
+        return LossGraph::new(outputs, *self);
+    }
+}
+
+// A loss node over the outputs of a model and one target placeholder per output.
+// The graph is built once; per sample only the inputs of the model and the
+// target values are written before `forward` and `backward` run again over the
+// cached graph.
+pub struct LossGraph {
+    targets: Vec<ValueRef>,
+    loss: ValueRef,
+}
+
+impl LossGraph {
+    pub fn new(outputs: &[ValueRef], loss: Loss) -> LossGraph {
+        assert!(!outputs.is_empty());
+        let targets: Vec<ValueRef> = outputs.iter().map(|_x| Value::from(0.0)).collect();
+        let loss = match loss {
+            Loss::MeanSquared => {
+                let squares: Vec<ValueRef> = outputs
+                    .iter()
+                    .zip(targets.iter())
+                    .map(|(output, target)| Engine::pow(&Engine::add(output, &Engine::inv(target))))
+                    .collect();
+                Engine::mul(
+                    &Engine::sum(&squares),
+                    &Value::from(1.0 / outputs.len() as f64),
+                )
+            }
+            Loss::CrossEntropy => {
+                // log-sum-exp shifted by the maximum, the shift cancels in the gradient
+                let shift = Engine::detach(&Engine::max(outputs));
+                let exps: Vec<ValueRef> = outputs
+                    .iter()
+                    .map(|output| Engine::exp(&Engine::add(output, &Engine::inv(&shift))))
+                    .collect();
+                let log_normalizer = Engine::add(&Engine::log(&Engine::sum(&exps)), &shift);
+                let terms: Vec<ValueRef> = outputs
+                    .iter()
+                    .zip(targets.iter())
+                    .map(|(output, target)| {
+                        Engine::mul(target, &Engine::add(&log_normalizer, &Engine::inv(output)))
+                    })
+                    .collect();
+                Engine::sum(&terms)
+            }
+        };
+        return LossGraph { targets, loss };
+    }
+
+    pub fn loss(&self) -> ValueRef {
+        return self.loss.clone();
+    }
+
+    pub fn targets(&self) -> Vec<ValueRef> {
+        return self.targets.clone();
+    }
+
+    pub fn set_targets(&self, targets: &[f64]) {
+        assert_eq!(targets.len(), self.targets.len());
+        for (placeholder, target) in self.targets.iter().zip(targets.iter()) {
+            placeholder.borrow_mut().value = *target;
+        }
+    }
+
+    // Writes the targets and recomputes the loss, the inputs of the model must
+    // already be set.
+    pub fn evaluate(&self, targets: &[f64]) -> f64 {
+        self.set_targets(targets);
+        self.loss.borrow_mut().forward();
+        return self.loss.borrow().value;
+    }
+
+    // Evaluates the loss and adds `scale` times its gradient to the parameters.
+    pub fn backward(&self, targets: &[f64], scale: f64) -> f64 {
+        let value = self.evaluate(targets);
+        self.loss.borrow_mut().grad = scale;
+        self.loss.borrow_mut().backward();
+        return value;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_mean_squared_reuses_graph() {
+        let outputs: Vec<ValueRef> = (0..2).map(|_x| Value::from(0.0)).collect();
+        outputs[0].borrow_mut().needs_grad = true;
+        let graph = Loss::MeanSquared.build(&outputs);
+
+        outputs[0].borrow_mut().value = 3.0;
+        outputs[1].borrow_mut().value = 1.0;
+        assert_eq!(graph.evaluate(&[1.0, 1.0]), 2.0);
+        assert_eq!(graph.backward(&[2.0, 0.0], 1.0), 1.0);
+        assert_eq!(outputs[0].borrow().grad, 1.0);
+        // the same nodes, new target values
+        let loss = graph.loss();
+        assert_eq!(graph.evaluate(&[3.0, 1.0]), 0.0);
+        assert!(Rc::ptr_eq(&loss, &graph.loss()));
+    }
+
+    #[test]
+    fn test_cross_entropy() {
+        let logits = [2.0, -1.0, 0.5];
+        let outputs: Vec<ValueRef> = logits.iter().map(|x| Value::from(*x)).collect();
+        for output in outputs.iter() {
+            output.borrow_mut().needs_grad = true;
+        }
+        let graph = Loss::CrossEntropy.build(&outputs);
+        let target = [0.0, 0.0, 1.0];
+        let normalizer: f64 = logits.iter().map(|x: &f64| x.exp()).sum();
+        let value = graph.backward(&target, 1.0);
+        assert!((value - (normalizer.ln() - 0.5)).abs() < 1e-12);
+        // d loss / d logit = softmax - target
+        for index in 0..3 {
+            let expected = logits[index].exp() / normalizer - target[index];
+            assert!((outputs[index].borrow().grad - expected).abs() < 1e-12);
+        }
+
+        // large logits don't overflow
+        outputs[0].borrow_mut().value = 1000.0;
+        assert!((graph.evaluate(&[1.0, 0.0, 0.0])).abs() < 1e-12);
+    }
+}
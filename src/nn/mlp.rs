@@ -1,31 +1,96 @@
 use crate::engine::Value;
 use crate::engine::ValueRef;
+use crate::nn::Activation;
 use crate::nn::Layer;
+use crate::nn::Module;
+use crate::nn::Normalization;
 
 pub struct MLP {
     layers: Vec<Layer>,
+    inputs: Vec<ValueRef>,
+    // one per layer, normalizing that layer's inputs
+    norms: Vec<Box<dyn Module>>,
+    normalization: Normalization,
 }
 
 impl MLP {
     pub fn new(sizes: Vec<usize>, linear_config: Vec<bool>, input_size: usize) -> MLP {
+        let activations = linear_config
+            .iter()
+            .map(|linear| Activation::from_linear(*linear))
+            .collect();
+        return MLP::with_activations(sizes, activations, input_size);
+    }
+
+    pub fn with_activations(
+        sizes: Vec<usize>,
+        activations: Vec<Activation>,
+        input_size: usize,
+    ) -> MLP {
+        return MLP::with_normalization(sizes, activations, Normalization::None, input_size);
+    }
+
+    pub fn with_normalization(
+        sizes: Vec<usize>,
+        activations: Vec<Activation>,
+        normalization: Normalization,
+        input_size: usize,
+    ) -> MLP {
+        assert_eq!(sizes.len(), activations.len());
         let mut layers: Vec<Layer> = vec![];
         layers.reserve(sizes.len());
+        let mut norms: Vec<Box<dyn Module>> = vec![];
         let inputs: Vec<ValueRef> = (0..input_size).map(|_x| Value::from(0.0)).collect();
-        let mut output: &Vec<ValueRef> = &inputs;
+        let mut output: Vec<ValueRef> = inputs.clone();
         for (index, size) in sizes.iter().enumerate() {
-            let layer = Layer::new(*size, output, linear_config[index]);
+            if let Some(norm) = normalization.build(&output) {
+                output = norm.outputs();
+                norms.push(norm);
+            }
+            let layer = Layer::with_activation(*size, &output, activations[index]);
             layers.push(layer);
-            output = &layers[index].outputs;
+            output = layers[index].outputs.clone();
         }
 
-        return MLP { layers };
+        return MLP {
+            layers,
+            inputs,
+            norms,
+            normalization,
+        };
     }
 
     pub fn set(&mut self, inputs: Vec<f64>) {
-        self.layers[0].set(inputs);
+        assert_eq!(inputs.len(), self.inputs.len());
+
+        for (index, input) in inputs.iter().enumerate() {
+            self.inputs[index].borrow_mut().value = *input;
+        }
+    }
+
+    pub fn normalization(&self) -> Normalization {
+        return self.normalization;
+    }
+
+    pub fn sizes(&self) -> Vec<usize> {
+        return self
+            .layers
+            .iter()
+            .map(|layer| layer.outputs.len())
+            .collect();
     }
 
-    pub fn outputs(&self) -> Vec<ValueRef> {
+    pub fn activations(&self) -> Vec<Activation> {
+        return self.layers.iter().map(|layer| layer.activation()).collect();
+    }
+}
+
+impl Module for MLP {
+    fn inputs(&self) -> Vec<ValueRef> {
+        return self.inputs.clone();
+    }
+
+    fn outputs(&self) -> Vec<ValueRef> {
         let mut last_layer: Vec<ValueRef> = vec![];
         last_layer.reserve(self.layers[self.layers.len() - 1].outputs.len());
         for output in self.layers[self.layers.len() - 1].outputs.iter() {
@@ -35,15 +100,65 @@ impl MLP {
         return last_layer;
     }
 
-    pub fn zero_grad(&mut self) {
-        for layer in self.layers.iter_mut() {
-            layer.zero_grad();
+    fn parameters(&self) -> Vec<ValueRef> {
+        return self
+            .named_parameters()
+            .into_iter()
+            .map(|(_, parameter)| parameter)
+            .collect();
+    }
+
+    // Normalization parameters come after all dense layers.
+    fn named_parameters(&self) -> Vec<(String, ValueRef)> {
+        let mut named: Vec<(String, ValueRef)> = Vec::with_capacity(self.parameter_count());
+        for (index, layer) in self.layers.iter().enumerate() {
+            for (name, parameter) in layer.named_parameters() {
+                named.push((format!("layers.{}.{}", index, name), parameter));
+            }
+        }
+        for (index, norm) in self.norms.iter().enumerate() {
+            for (name, parameter) in norm.named_parameters() {
+                named.push((format!("norms.{}.{}", index, name), parameter));
+            }
         }
+        return named;
     }
 
-    pub fn update(&self, alpha: f64) {
-        for layer in self.layers.iter() {
-            layer.update(alpha);
+    fn parameter_count(&self) -> usize {
+        let layers: usize = self.layers.iter().map(|l| l.parameter_count()).sum();
+        let norms: usize = self.norms.iter().map(|n| n.parameter_count()).sum();
+        return layers + norms;
+    }
+
+    fn before_forward(&mut self) {
+        for norm in self.norms.iter_mut() {
+            norm.before_forward();
+        }
+    }
+
+    fn after_forward(&mut self) {
+        for norm in self.norms.iter_mut() {
+            norm.after_forward();
+        }
+    }
+
+    fn buffers(&self) -> Vec<f64> {
+        return self.norms.iter().flat_map(|norm| norm.buffers()).collect();
+    }
+
+    fn set_buffers(&mut self, buffers: &[f64]) {
+        let mut offset = 0;
+        for norm in self.norms.iter_mut() {
+            let count = norm.buffers().len();
+            norm.set_buffers(&buffers[offset..offset + count]);
+            offset += count;
+        }
+        assert_eq!(offset, buffers.len());
+    }
+
+    fn train(&mut self, training: bool) {
+        for norm in self.norms.iter_mut() {
+            norm.train(training);
         }
     }
 }
@@ -51,6 +166,7 @@ impl MLP {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::engine::randomize;
     use crate::engine::Engine;
     use crate::engine::Value;
     use crate::engine::VALUE_RANDOM_SEED;
@@ -61,7 +177,7 @@ mod test {
 
         let mut net = MLP::new(vec![4, 6, 4], vec![false, false, true], 4);
         let values = vec![1.0, 0.0, 0.0, -2.0];
-        let alpha = 0.01;
+        let alpha = 0.03;
         net.set(values.clone());
         let outputs = net.outputs();
 
@@ -88,4 +204,109 @@ mod test {
         println!("{}", loss.borrow().value);
         assert!(loss.borrow().value < 1e-8);
     }
+
+    #[test]
+    fn test_module_forward() {
+        let mut net = MLP::new(vec![3, 2], vec![false, true], 2);
+        let outputs = net.forward(&[0.5, -1.0]);
+        assert_eq!(outputs.len(), 2);
+
+        net.set(vec![0.5, -1.0]);
+        for (value, output) in outputs.iter().zip(net.outputs().iter()) {
+            output.borrow_mut().forward();
+            assert_eq!(*value, output.borrow().value);
+        }
+    }
+
+    #[test]
+    fn test_softmax_output() {
+        let mut net =
+            MLP::with_activations(vec![5, 3], vec![Activation::Tanh, Activation::Softmax], 2);
+        assert_eq!(
+            net.activations(),
+            vec![Activation::Tanh, Activation::Softmax]
+        );
+        let outputs = net.forward(&[0.3, -0.7]);
+        assert!((outputs.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_normalized_raw_pixels() {
+        for normalization in [Normalization::Layer, Normalization::Running] {
+            let mut net = MLP::with_normalization(
+                vec![6, 6, 1],
+                vec![Activation::Tanh, Activation::Tanh, Activation::Identity],
+                normalization,
+                4,
+            );
+            assert_eq!(net.parameter_count(), 6 * 5 + 6 * 7 + 7 + 2 * (4 + 6 + 6));
+            assert_eq!(net.named_parameters().last().unwrap().0, "norms.2.bias.5");
+
+            let weights: Vec<ValueRef> = net
+                .named_parameters()
+                .into_iter()
+                .filter(|(name, _)| name.starts_with("layers."))
+                .map(|(_, parameter)| parameter)
+                .collect();
+            randomize(&weights, 1.0, 3);
+
+            let samples = [
+                ([0.0, 255.0, 128.0, 3.0], 1.0),
+                ([255.0, 0.0, 7.0, 200.0], -1.0),
+            ];
+            let output = net.outputs()[0].clone();
+            let target = Value::from(0.0);
+            let loss = Engine::pow(&Engine::add(&output, &Engine::inv(&target)));
+            let mut losses = vec![];
+            for _ in 0..200 {
+                let mut total = 0.0;
+                for (pixels, label) in samples.iter() {
+                    net.forward(pixels);
+                    target.borrow_mut().value = *label;
+                    loss.borrow_mut().forward();
+                    total += loss.borrow().value;
+
+                    net.zero_grad();
+                    loss.borrow_mut().grad = 1.0;
+                    loss.borrow_mut().backward();
+                    net.update(0.05);
+                }
+                losses.push(total);
+            }
+            assert!(
+                losses[199] < 0.01 * losses[0],
+                "{:?}: {} -> {}",
+                normalization,
+                losses[0],
+                losses[199]
+            );
+
+            // frozen statistics give repeatable outputs
+            net.eval();
+            let buffers = net.buffers();
+            let first = net.forward(&samples[0].0);
+            net.forward(&samples[1].0);
+            assert_eq!(net.forward(&samples[0].0), first);
+            assert_eq!(net.buffers(), buffers);
+        }
+    }
+
+    #[test]
+    fn test_named_parameters() {
+        let net = MLP::new(vec![3, 2], vec![false, true], 4);
+        assert_eq!(net.parameter_count(), 3 * 5 + 2 * 4);
+
+        let parameters = net.parameters();
+        let named = net.named_parameters();
+        assert_eq!(parameters.len(), net.parameter_count());
+        assert_eq!(named.len(), parameters.len());
+        for ((_, named), parameter) in named.iter().zip(parameters.iter()) {
+            assert!(std::rc::Rc::ptr_eq(named, parameter));
+        }
+
+        assert_eq!(named[0].0, "layers.0.neurons.0.bias");
+        assert_eq!(named[1].0, "layers.0.neurons.0.weight.0");
+        assert_eq!(named[5].0, "layers.0.neurons.1.bias");
+        assert_eq!(named[named.len() - 1].0, "layers.1.neurons.1.weight.2");
+    }
 }
@@ -1,7 +1,49 @@
+mod activation;
+mod attention;
 mod neuron;
+mod conv;
+mod dropout;
+mod embedding;
+mod graph;
 mod layer;
+mod linear;
+mod loss;
 mod mlp;
+mod module;
+mod norm;
+mod pool;
+mod reshape;
+mod rnn;
+mod sequential;
+mod transformer;
 
+pub use activation::Activation;
+pub use activation::ActivationLayer;
+pub use attention::scaled_dot_product_attention;
+pub use attention::MultiHeadAttention;
 pub use neuron::Neuron;
+pub use conv::Conv1d;
+pub use conv::Conv2d;
+pub use dropout::Dropout;
+pub use embedding::Embedding;
+pub use graph::Graph;
 pub use layer::Layer;
+pub use linear::Linear;
+pub use loss::Loss;
+pub use loss::LossGraph;
 pub use mlp::MLP;
+pub use module::Module;
+pub use norm::BatchNorm1d;
+pub use norm::RunningNorm;
+pub use norm::LayerNorm;
+pub use norm::Normalization;
+pub use pool::Pool;
+pub use pool::PoolKind;
+pub use reshape::Reshape;
+pub use rnn::GruCell;
+pub use rnn::LstmCell;
+pub use rnn::RecurrentCell;
+pub use rnn::Recurrent;
+pub use rnn::RnnCell;
+pub use sequential::Sequential;
+pub use transformer::TransformerBlock;
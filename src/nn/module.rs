@@ -0,0 +1,91 @@
+use crate::engine::Value;
+use crate::engine::ValueRef;
+
+// Common interface of everything that can be placed in a model. A module owns a
+// fixed graph from its input nodes to its output nodes; `forward` writes new input
+// values and recomputes the outputs.
+pub trait Module {
+    fn inputs(&self) -> Vec<ValueRef>;
+
+    fn outputs(&self) -> Vec<ValueRef>;
+
+    fn parameters(&self) -> Vec<ValueRef>;
+
+    // Logical layout of the inputs and outputs, e.g. `[channels, height, width]`.
+    // Containers check that consecutive modules agree on it.
+    fn input_shape(&self) -> Vec<usize> {
+        return vec![self.inputs().len()];
+    }
+
+    fn output_shape(&self) -> Vec<usize> {
+        return vec![self.outputs().len()];
+    }
+
+    fn named_parameters(&self) -> Vec<(String, ValueRef)> {
+        return self
+            .parameters()
+            .into_iter()
+            .enumerate()
+            .map(|(index, parameter)| (index.to_string(), parameter))
+            .collect();
+    }
+
+    fn parameter_count(&self) -> usize {
+        return self.parameters().len();
+    }
+
+    // Non-trainable state that changes the outputs, e.g. running statistics. Saved
+    // and restored together with the parameters.
+    fn buffers(&self) -> Vec<f64> {
+        return vec![];
+    }
+
+    fn set_buffers(&mut self, buffers: &[f64]) {
+        assert!(buffers.is_empty());
+    }
+
+    // Hooks around every `forward`, e.g. for resampling dropout masks or updating
+    // running statistics. When a loss graph is driven through `Value::forward`
+    // directly, call them by hand.
+    fn before_forward(&mut self) {}
+
+    fn after_forward(&mut self) {}
+
+    fn forward(&mut self, inputs: &[f64]) -> Vec<f64> {
+        self.before_forward();
+        let nodes = self.inputs();
+        assert_eq!(inputs.len(), nodes.len());
+        for (node, input) in nodes.iter().zip(inputs.iter()) {
+            node.borrow_mut().value = *input;
+        }
+
+        let outputs = self.outputs();
+        Value::forward_all(&outputs);
+        self.after_forward();
+        return outputs.iter().map(|output| output.borrow().value).collect();
+    }
+
+    // Modules behaving differently during training (e.g. dropout) override this.
+    fn train(&mut self, _training: bool) {}
+
+    fn eval(&mut self) {
+        self.train(false);
+    }
+
+    fn zero_grad(&self) {
+        for parameter in self.parameters().iter() {
+            parameter.borrow_mut().zero_grad();
+        }
+    }
+
+    fn update(&self, alpha: f64) {
+        for parameter in self.parameters().iter() {
+            if !parameter.borrow().needs_grad {
+                continue;
+            }
+
+            let v = { parameter.borrow().grad };
+            parameter.borrow_mut().value -= v * alpha;
+        }
+    }
+}
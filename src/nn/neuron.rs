@@ -1,6 +1,8 @@
 use crate::engine::Engine;
 use crate::engine::Value;
 use crate::engine::ValueRef;
+use crate::nn::Activation;
+use crate::nn::Module;
 
 pub struct Neuron {
     parameters: Vec<ValueRef>,
@@ -10,6 +12,11 @@ pub struct Neuron {
 
 impl Neuron {
     pub fn new(inputs: &Vec<ValueRef>, linear: bool) -> Neuron {
+        return Neuron::with_activation(inputs, Activation::from_linear(linear));
+    }
+
+    pub fn with_activation(inputs: &Vec<ValueRef>, activation: Activation) -> Neuron {
+        assert!(activation.is_elementwise());
         let size = inputs.len();
         let parameters: Vec<ValueRef> = (0..(size + 1)).map(|_x| Value::random()).collect();
         let mut input_refs: Vec<ValueRef> = vec![];
@@ -34,36 +41,42 @@ impl Neuron {
         return Neuron {
             parameters,
             inputs: input_refs,
-            output: if linear {
-                output
-            } else {
-                Engine::relu(&output)
-            },
+            output: activation.apply(&output),
         };
     }
 
-    pub fn zero_grad(&self) {
-        for param in self.parameters.iter() {
-            param.borrow_mut().zero_grad();
+    pub fn set(&self, inputs: Vec<f64>) {
+        assert_eq!(inputs.len(), self.inputs.len());
+
+        for (index, input) in inputs.iter().enumerate() {
+            self.inputs[index].borrow_mut().value = *input;
         }
     }
+}
 
-    pub fn update(&self, alpha: f64) {
-        for param in self.parameters.iter() {
-            if !param.borrow().needs_grad {
-                continue;
-            }
+impl Module for Neuron {
+    fn inputs(&self) -> Vec<ValueRef> {
+        return self.inputs.clone();
+    }
 
-            let v = { param.borrow().grad };
-            param.borrow_mut().value -= v * alpha;
-        }
+    fn outputs(&self) -> Vec<ValueRef> {
+        return vec![self.output.clone()];
     }
 
-    pub fn set(&self, inputs: Vec<f64>) {
-        assert_eq!(inputs.len(), self.inputs.len());
+    // Parameter 0 is the bias, the weights follow in input order.
+    fn parameters(&self) -> Vec<ValueRef> {
+        return self.parameters.clone();
+    }
 
-        for (index, input) in inputs.iter().enumerate() {
-            self.inputs[index].borrow_mut().value = *input;
+    fn named_parameters(&self) -> Vec<(String, ValueRef)> {
+        let mut named: Vec<(String, ValueRef)> = Vec::with_capacity(self.parameters.len());
+        for (index, parameter) in self.parameters.iter().enumerate() {
+            let name = match index {
+                0 => String::from("bias"),
+                _ => format!("weight.{}", index - 1),
+            };
+            named.push((name, parameter.clone()));
         }
+        return named;
     }
 }
@@ -0,0 +1,627 @@
+use crate::engine::Engine;
+use crate::engine::Value;
+use crate::engine::ValueRef;
+use crate::nn::Module;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Normalization {
+    None,
+    Running,
+    Layer,
+}
+
+impl Normalization {
+    pub fn build(&self, inputs: &[ValueRef]) -> Option<Box<dyn Module>> {
+        return match self {
+            Normalization::None => None,
+            Normalization::Running => Some(Box::new(RunningNorm::new(inputs, 0.1, 1e-5))),
+            Normalization::Layer => Some(Box::new(LayerNorm::new(inputs, 1e-5))),
+        };
+    }
+}
+
+fn affine_parameters(size: usize) -> (Vec<ValueRef>, Vec<ValueRef>) {
+    let gains: Vec<ValueRef> = (0..size).map(|_x| Value::from(1.0)).collect();
+    let biases: Vec<ValueRef> = (0..size).map(|_x| Value::from(0.0)).collect();
+    for parameter in gains.iter().chain(biases.iter()) {
+        parameter.borrow_mut().needs_grad = true;
+    }
+    return (gains, biases);
+}
+
+fn named_affine_parameters(gains: &[ValueRef], biases: &[ValueRef]) -> Vec<(String, ValueRef)> {
+    let mut named: Vec<(String, ValueRef)> = Vec::with_capacity(gains.len() + biases.len());
+    for (index, gain) in gains.iter().enumerate() {
+        named.push((format!("weight.{}", index), gain.clone()));
+    }
+    for (index, bias) in biases.iter().enumerate() {
+        named.push((format!("bias.{}", index), bias.clone()));
+    }
+    return named;
+}
+
+// Normalizes every feature with a running mean and variance, followed by a learnable
+// gain and bias. Per-sample graphs such as `MLP` have no batch statistics, see
+// `BatchNorm1d` for normalizing over a mini-batch. In training mode every forward
+// pass updates the statistics and the next pass is normalized with them; in eval
+// mode they stay frozen. The statistics are constants of the graph, gradients flow
+// into the inputs, gains and biases only.
+//
+// The first samples are averaged exactly (rate 1/n) until that drops below the
+// momentum, so unscaled inputs such as raw pixels are normalized after the first
+// pass instead of converging slowly from mean 0 and variance 1.
+pub struct RunningNorm {
+    inputs: Vec<ValueRef>,
+    outputs: Vec<ValueRef>,
+    gains: Vec<ValueRef>,
+    biases: Vec<ValueRef>,
+    means: Vec<ValueRef>,
+    inverse_deviations: Vec<ValueRef>,
+    running_mean: Vec<f64>,
+    running_variance: Vec<f64>,
+    // training samples seen so far
+    count: usize,
+    momentum: f64,
+    epsilon: f64,
+    training: bool,
+}
+
+impl RunningNorm {
+    pub fn new(inputs: &[ValueRef], momentum: f64, epsilon: f64) -> RunningNorm {
+        assert!(momentum > 0.0 && momentum <= 1.0);
+        let size = inputs.len();
+        let (gains, biases) = affine_parameters(size);
+        let means: Vec<ValueRef> = (0..size).map(|_x| Value::from(0.0)).collect();
+        let inverse_deviations: Vec<ValueRef> = (0..size)
+            .map(|_x| Value::from(1.0 / (1.0 + epsilon).sqrt()))
+            .collect();
+
+        let mut outputs: Vec<ValueRef> = Vec::with_capacity(size);
+        for index in 0..size {
+            let centered = Engine::add(&inputs[index], &Engine::inv(&means[index]));
+            let normalized = Engine::mul(&centered, &inverse_deviations[index]);
+            outputs.push(Engine::add(
+                &Engine::mul(&normalized, &gains[index]),
+                &biases[index],
+            ));
+        }
+
+        return RunningNorm {
+            inputs: inputs.to_vec(),
+            outputs,
+            gains,
+            biases,
+            means,
+            inverse_deviations,
+            running_mean: vec![0.0; size],
+            running_variance: vec![1.0; size],
+            count: 0,
+            momentum,
+            epsilon,
+            training: true,
+        };
+    }
+
+    pub fn running_mean(&self) -> Vec<f64> {
+        return self.running_mean.clone();
+    }
+
+    pub fn running_variance(&self) -> Vec<f64> {
+        return self.running_variance.clone();
+    }
+
+    // Writes the running statistics into the constants of the graph.
+    fn sync_statistics(&self) {
+        for index in 0..self.inputs.len() {
+            self.means[index].borrow_mut().value = self.running_mean[index];
+            self.inverse_deviations[index].borrow_mut().value =
+                1.0 / (self.running_variance[index] + self.epsilon).sqrt();
+        }
+    }
+}
+
+impl Module for RunningNorm {
+    fn inputs(&self) -> Vec<ValueRef> {
+        return self.inputs.clone();
+    }
+
+    fn outputs(&self) -> Vec<ValueRef> {
+        return self.outputs.clone();
+    }
+
+    fn parameters(&self) -> Vec<ValueRef> {
+        return self
+            .gains
+            .iter()
+            .chain(self.biases.iter())
+            .cloned()
+            .collect();
+    }
+
+    fn named_parameters(&self) -> Vec<(String, ValueRef)> {
+        return named_affine_parameters(&self.gains, &self.biases);
+    }
+
+    // The running mean, the running variance and the number of samples seen.
+    fn buffers(&self) -> Vec<f64> {
+        return [
+            self.running_mean.clone(),
+            self.running_variance.clone(),
+            vec![self.count as f64],
+        ]
+        .concat();
+    }
+
+    fn set_buffers(&mut self, buffers: &[f64]) {
+        let size = self.inputs.len();
+        assert_eq!(buffers.len(), 2 * size + 1);
+        self.running_mean = buffers[..size].to_vec();
+        self.running_variance = buffers[size..2 * size].to_vec();
+        self.count = buffers[2 * size] as usize;
+        self.sync_statistics();
+    }
+
+    fn after_forward(&mut self) {
+        if !self.training {
+            return;
+        }
+
+        self.count += 1;
+        let rate = self.momentum.max(1.0 / self.count as f64);
+        for index in 0..self.inputs.len() {
+            let delta = self.inputs[index].borrow().value - self.running_mean[index];
+            self.running_mean[index] += rate * delta;
+            // a single sample has no variance, keep the initial one until the second
+            match self.count {
+                1 => {}
+                2 => self.running_variance[index] = (1.0 - rate) * rate * delta * delta,
+                _ => {
+                    self.running_variance[index] =
+                        (1.0 - rate) * (self.running_variance[index] + rate * delta * delta);
+                }
+            }
+        }
+        self.sync_statistics();
+    }
+
+    fn train(&mut self, training: bool) {
+        self.training = training;
+    }
+}
+
+// Batch normalization over a mini-batch of `[batch, features]` inputs. In training
+// mode every feature is normalized with the mean and (biased) variance of the batch,
+// which are nodes of the graph, so gradients also flow through them; the running
+// statistics follow the batch ones with `momentum` (unbiased variance). In eval
+// mode the running statistics are used instead. Like `RunningNorm`, the first
+// batches are averaged exactly.
+pub struct BatchNorm1d {
+    inputs: Vec<ValueRef>,
+    outputs: Vec<ValueRef>,
+    shape: Vec<usize>,
+    gains: Vec<ValueRef>,
+    biases: Vec<ValueRef>,
+    batch_means: Vec<ValueRef>,
+    batch_variances: Vec<ValueRef>,
+    // constants holding the running statistics
+    running_means: Vec<ValueRef>,
+    running_variances: Vec<ValueRef>,
+    // selects the batch (0) or the running (1) statistics
+    mode: ValueRef,
+    // training batches seen so far
+    count: usize,
+    momentum: f64,
+    training: bool,
+}
+
+impl BatchNorm1d {
+    pub fn new(inputs: &[ValueRef], shape: Vec<usize>, momentum: f64, epsilon: f64) -> BatchNorm1d {
+        assert_eq!(shape.len(), 2, "expected [batch, features]");
+        assert_eq!(inputs.len(), shape[0] * shape[1]);
+        assert!(shape[0] > 1, "batch statistics need at least two samples");
+        assert!(momentum > 0.0 && momentum <= 1.0);
+        let (batch, features) = (shape[0], shape[1]);
+        let (gains, biases) = affine_parameters(features);
+        let running_means: Vec<ValueRef> = (0..features).map(|_x| Value::from(0.0)).collect();
+        let running_variances: Vec<ValueRef> = (0..features).map(|_x| Value::from(1.0)).collect();
+        let mode = Value::from(0.0);
+        let scale = Value::from(1.0 / batch as f64);
+        let epsilon = Value::from(epsilon);
+
+        let mut batch_means: Vec<ValueRef> = Vec::with_capacity(features);
+        let mut batch_variances: Vec<ValueRef> = Vec::with_capacity(features);
+        let mut outputs: Vec<ValueRef> = inputs.to_vec();
+        for feature in 0..features {
+            let column: Vec<ValueRef> = (0..batch)
+                .map(|sample| inputs[sample * features + feature].clone())
+                .collect();
+            let batch_mean = Engine::mul(&Engine::sum(&column), &scale);
+            let negative_mean = Engine::inv(&batch_mean);
+            let squares: Vec<ValueRef> = column
+                .iter()
+                .map(|input| Engine::pow(&Engine::add(input, &negative_mean)))
+                .collect();
+            let batch_variance = Engine::mul(&Engine::sum(&squares), &scale);
+
+            let mean = Engine::select(&mode, &[batch_mean.clone(), running_means[feature].clone()]);
+            let variance = Engine::select(
+                &mode,
+                &[batch_variance.clone(), running_variances[feature].clone()],
+            );
+            let negative_mean = Engine::inv(&mean);
+            let inverse_deviation = Engine::powf(&Engine::add(&variance, &epsilon), -0.5);
+            for (sample, input) in column.iter().enumerate() {
+                let normalized =
+                    Engine::mul(&Engine::add(input, &negative_mean), &inverse_deviation);
+                outputs[sample * features + feature] =
+                    Engine::add(&Engine::mul(&normalized, &gains[feature]), &biases[feature]);
+            }
+            batch_means.push(batch_mean);
+            batch_variances.push(batch_variance);
+        }
+
+        return BatchNorm1d {
+            inputs: inputs.to_vec(),
+            outputs,
+            shape,
+            gains,
+            biases,
+            batch_means,
+            batch_variances,
+            running_means,
+            running_variances,
+            mode,
+            count: 0,
+            momentum,
+            training: true,
+        };
+    }
+
+    pub fn running_mean(&self) -> Vec<f64> {
+        return self
+            .running_means
+            .iter()
+            .map(|node| node.borrow().value)
+            .collect();
+    }
+
+    pub fn running_variance(&self) -> Vec<f64> {
+        return self
+            .running_variances
+            .iter()
+            .map(|node| node.borrow().value)
+            .collect();
+    }
+}
+
+impl Module for BatchNorm1d {
+    fn inputs(&self) -> Vec<ValueRef> {
+        return self.inputs.clone();
+    }
+
+    fn outputs(&self) -> Vec<ValueRef> {
+        return self.outputs.clone();
+    }
+
+    fn parameters(&self) -> Vec<ValueRef> {
+        return self
+            .gains
+            .iter()
+            .chain(self.biases.iter())
+            .cloned()
+            .collect();
+    }
+
+    fn named_parameters(&self) -> Vec<(String, ValueRef)> {
+        return named_affine_parameters(&self.gains, &self.biases);
+    }
+
+    fn input_shape(&self) -> Vec<usize> {
+        return self.shape.clone();
+    }
+
+    fn output_shape(&self) -> Vec<usize> {
+        return self.shape.clone();
+    }
+
+    // The running mean, the running variance and the number of batches seen.
+    fn buffers(&self) -> Vec<f64> {
+        return [
+            self.running_mean(),
+            self.running_variance(),
+            vec![self.count as f64],
+        ]
+        .concat();
+    }
+
+    fn set_buffers(&mut self, buffers: &[f64]) {
+        let features = self.shape[1];
+        assert_eq!(buffers.len(), 2 * features + 1);
+        for feature in 0..features {
+            self.running_means[feature].borrow_mut().value = buffers[feature];
+            self.running_variances[feature].borrow_mut().value = buffers[features + feature];
+        }
+        self.count = buffers[2 * features] as usize;
+    }
+
+    // Reads the batch statistics of the pass that just ran.
+    fn after_forward(&mut self) {
+        if !self.training {
+            return;
+        }
+
+        self.count += 1;
+        let rate = self.momentum.max(1.0 / self.count as f64);
+        let batch = self.shape[0] as f64;
+        for feature in 0..self.shape[1] {
+            let mean = self.batch_means[feature].borrow().value;
+            let variance = self.batch_variances[feature].borrow().value * batch / (batch - 1.0);
+            let mut running_mean = self.running_means[feature].borrow_mut();
+            running_mean.value += rate * (mean - running_mean.value);
+            let mut running_variance = self.running_variances[feature].borrow_mut();
+            running_variance.value += rate * (variance - running_variance.value);
+        }
+    }
+
+    fn train(&mut self, training: bool) {
+        self.training = training;
+        self.mode.borrow_mut().value = match training {
+            true => 0.0,
+            false => 1.0,
+        };
+    }
+}
+
+// Normalizes each sample across its features, the statistics are part of the graph.
+pub struct LayerNorm {
+    inputs: Vec<ValueRef>,
+    outputs: Vec<ValueRef>,
+    gains: Vec<ValueRef>,
+    biases: Vec<ValueRef>,
+    epsilon: f64,
+}
+
+impl LayerNorm {
+    pub fn new(inputs: &[ValueRef], epsilon: f64) -> LayerNorm {
+        assert!(!inputs.is_empty());
+        let mut norm = LayerNorm::unbound(inputs.len(), epsilon);
+        norm.inputs = inputs.to_vec();
+        norm.outputs = norm.apply(inputs);
+        return norm;
+    }
+
+    // Only the gains and biases for `size` features, without inputs or outputs, for
+    // normalizing through `apply` like a `Linear`.
+    pub fn unbound(size: usize, epsilon: f64) -> LayerNorm {
+        assert!(size > 0);
+        let (gains, biases) = affine_parameters(size);
+        return LayerNorm {
+            inputs: vec![],
+            outputs: vec![],
+            gains,
+            biases,
+            epsilon,
+        };
+    }
+
+    // Normalizes further inputs with the same gains and biases, e.g. every position
+    // of a sequence.
+    pub fn apply(&self, inputs: &[ValueRef]) -> Vec<ValueRef> {
+        let size = inputs.len();
+        assert_eq!(size, self.gains.len());
+        let scale = Value::from(1.0 / size as f64);
+
+        let mean = Engine::mul(&Engine::sum(inputs), &scale);
+        let negative_mean = Engine::inv(&mean);
+        let centered: Vec<ValueRef> = inputs
+            .iter()
+            .map(|input| Engine::add(input, &negative_mean))
+            .collect();
+        let squares: Vec<ValueRef> = centered.iter().map(Engine::pow).collect();
+        let variance = Engine::mul(&Engine::sum(&squares), &scale);
+        let inverse_deviation =
+            Engine::powf(&Engine::add(&variance, &Value::from(self.epsilon)), -0.5);
+
+        let mut outputs: Vec<ValueRef> = Vec::with_capacity(size);
+        for ((centered, gain), bias) in centered.iter().zip(&self.gains).zip(&self.biases) {
+            let normalized = Engine::mul(centered, &inverse_deviation);
+            outputs.push(Engine::add(&Engine::mul(&normalized, gain), bias));
+        }
+        return outputs;
+    }
+}
+
+impl Module for LayerNorm {
+    fn inputs(&self) -> Vec<ValueRef> {
+        return self.inputs.clone();
+    }
+
+    fn outputs(&self) -> Vec<ValueRef> {
+        return self.outputs.clone();
+    }
+
+    fn parameters(&self) -> Vec<ValueRef> {
+        return self
+            .gains
+            .iter()
+            .chain(self.biases.iter())
+            .cloned()
+            .collect();
+    }
+
+    fn named_parameters(&self) -> Vec<(String, ValueRef)> {
+        return named_affine_parameters(&self.gains, &self.biases);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::engine::randomize;
+    use crate::nn::Linear;
+
+    #[test]
+    fn test_layer_norm() {
+        let inputs: Vec<ValueRef> = [1.0, 4.0, -2.0, 7.0]
+            .iter()
+            .map(|x| Value::from(*x))
+            .collect();
+        for input in inputs.iter() {
+            input.borrow_mut().needs_grad = true;
+        }
+        let mut norm = LayerNorm::new(&inputs, 1e-5);
+        let outputs = norm.forward(&[1.0, 4.0, -2.0, 7.0]);
+        let mean = outputs.iter().sum::<f64>() / 4.0;
+        let variance = outputs.iter().map(|o| (o - mean) * (o - mean)).sum::<f64>() / 4.0;
+        assert!(mean.abs() < 1e-12);
+        assert!((variance - 1.0).abs() < 1e-5);
+
+        // d output[1] / d input[0] against finite differences
+        let output = norm.outputs()[1].clone();
+        output.borrow_mut().grad = 1.0;
+        output.borrow_mut().backward();
+        let epsilon = 1e-6;
+        let plus = norm.forward(&[1.0 + epsilon, 4.0, -2.0, 7.0])[1];
+        let minus = norm.forward(&[1.0 - epsilon, 4.0, -2.0, 7.0])[1];
+        let numeric = (plus - minus) / (2.0 * epsilon);
+        assert!((inputs[0].borrow().grad - numeric).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_running_norm_statistics() {
+        let inputs: Vec<ValueRef> = (0..2).map(|_x| Value::from(0.0)).collect();
+        let mut norm = RunningNorm::new(&inputs, 0.1, 1e-5);
+        // the first samples are averaged exactly
+        norm.forward(&[10.0, 0.0]);
+        assert_eq!(norm.running_mean(), vec![10.0, 0.0]);
+        assert_eq!(norm.running_variance(), vec![1.0, 1.0]);
+        norm.forward(&[20.0, 0.0]);
+        assert_eq!(norm.running_mean(), vec![15.0, 0.0]);
+        assert_eq!(norm.running_variance()[0], 25.0);
+        norm.forward(&[30.0, 0.0]);
+        assert_eq!(norm.running_mean()[0], 20.0);
+        assert!((norm.running_variance()[0] - 200.0 / 3.0).abs() < 1e-9);
+
+        for sample in 0..2000 {
+            let offset = if sample % 2 == 0 { 1.0 } else { -1.0 };
+            norm.forward(&[200.0 + offset, -50.0 - 3.0 * offset]);
+        }
+        assert!((norm.running_mean()[0] - 200.0).abs() < 0.2);
+        assert!((norm.running_variance()[1] - 9.0).abs() < 1.0);
+        let outputs = norm.forward(&[201.0, -53.0]);
+        assert!((outputs[0] - 1.0).abs() < 0.2 && (outputs[1] + 1.0).abs() < 0.2);
+
+        norm.eval();
+        let frozen = norm.running_mean();
+        norm.forward(&[1000.0, 1000.0]);
+        assert_eq!(norm.running_mean(), frozen);
+    }
+
+    #[test]
+    fn test_batch_norm() {
+        let batch = [1.0, 10.0, 2.0, 20.0, 6.0, 0.0];
+        let inputs: Vec<ValueRef> = batch.iter().map(|x| Value::from(*x)).collect();
+        for input in inputs.iter() {
+            input.borrow_mut().needs_grad = true;
+        }
+        let mut norm = BatchNorm1d::new(&inputs, vec![3, 2], 0.1, 1e-5);
+        let outputs = norm.forward(&batch);
+        for feature in 0..2 {
+            let column: Vec<f64> = (0..3).map(|sample| outputs[sample * 2 + feature]).collect();
+            let mean = column.iter().sum::<f64>() / 3.0;
+            let variance = column.iter().map(|o| (o - mean) * (o - mean)).sum::<f64>() / 3.0;
+            assert!(mean.abs() < 1e-12);
+            assert!((variance - 1.0).abs() < 1e-5);
+        }
+        // the first batch is taken as is, with the unbiased variance
+        assert_eq!(norm.running_mean(), vec![3.0, 10.0]);
+        let variance = norm.running_variance();
+        assert!((variance[0] - 7.0).abs() < 1e-12 && (variance[1] - 100.0).abs() < 1e-12);
+
+        // the batch statistics are part of the graph: d output[0] / d input[2]
+        let output = norm.outputs()[0].clone();
+        output.borrow_mut().grad = 1.0;
+        output.borrow_mut().backward();
+        let epsilon = 1e-6;
+        let mut shifted = batch;
+        shifted[2] += epsilon;
+        let plus = norm.forward(&shifted)[0];
+        shifted[2] -= 2.0 * epsilon;
+        let minus = norm.forward(&shifted)[0];
+        let numeric = (plus - minus) / (2.0 * epsilon);
+        assert!(numeric.abs() > 0.1);
+        assert!((inputs[2].borrow().grad - numeric).abs() < 1e-6);
+
+        norm.eval();
+        norm.set_buffers(&[0.0, 10.0, 4.0, 100.0, 1.0]);
+        let outputs = norm.forward(&[2.0, 30.0, 0.0, 10.0, -2.0, 0.0]);
+        assert!((outputs[0] - 1.0).abs() < 1e-5 && (outputs[1] - 2.0).abs() < 1e-5);
+        assert_eq!(norm.buffers(), vec![0.0, 10.0, 4.0, 100.0, 1.0]);
+    }
+
+    #[test]
+    fn test_batch_norm_trains_on_raw_pixels() {
+        // a batch norm in front of a two layer perceptron, applied to every sample
+        let batch = [
+            0.0, 255.0, 128.0, 255.0, 0.0, 7.0, 30.0, 240.0, 250.0, 200.0, 10.0, 0.0,
+        ];
+        let targets = [1.0, -1.0, 1.0, -1.0];
+        let inputs: Vec<ValueRef> = batch.iter().map(|_x| Value::from(0.0)).collect();
+        let mut norm = BatchNorm1d::new(&inputs, vec![4, 3], 0.1, 1e-5);
+        let (hidden, last) = (Linear::new(3, 6), Linear::new(6, 1));
+        let mut parameters = norm.parameters();
+        for (prefix, linear) in [("hidden", &hidden), ("last", &last)] {
+            let weights: Vec<ValueRef> = linear
+                .named_parameters(prefix)
+                .into_iter()
+                .map(|(_, parameter)| parameter)
+                .collect();
+            randomize(&weights, 1.0, 9);
+            parameters.extend(weights);
+        }
+
+        let normalized = norm.outputs();
+        let mut errors: Vec<ValueRef> = vec![];
+        for (sample, target) in targets.iter().enumerate() {
+            let features = &normalized[sample * 3..(sample + 1) * 3];
+            let activations: Vec<ValueRef> =
+                hidden.apply(features).iter().map(Engine::tanh).collect();
+            let error = Engine::add(&last.apply(&activations)[0], &Value::from(-target));
+            errors.push(Engine::pow(&error));
+        }
+        let loss = Engine::sum(&errors);
+
+        let mut losses = vec![];
+        for _ in 0..200 {
+            norm.forward(&batch);
+            loss.borrow_mut().forward();
+            losses.push(loss.borrow().value);
+            for parameter in parameters.iter() {
+                parameter.borrow_mut().zero_grad();
+            }
+            loss.borrow_mut().grad = 1.0;
+            loss.borrow_mut().backward();
+            for parameter in parameters.iter() {
+                let grad = { parameter.borrow().grad };
+                parameter.borrow_mut().value -= 0.05 * grad;
+            }
+        }
+        assert!(
+            losses[199] < 0.01 * losses[0],
+            "{} -> {}",
+            losses[0],
+            losses[199]
+        );
+
+        // the running statistics of a repeated batch are its mean and variance
+        norm.eval();
+        norm.forward(&batch);
+        loss.borrow_mut().forward();
+        assert!((norm.running_mean()[0] - 121.25).abs() < 1e-9);
+        assert!(
+            loss.borrow().value < 0.1 * losses[0],
+            "{}",
+            loss.borrow().value
+        );
+    }
+}
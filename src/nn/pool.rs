@@ -0,0 +1,205 @@
+use crate::engine::Engine;
+use crate::engine::Value;
+use crate::engine::ValueRef;
+use crate::nn::Module;
+
+// Windows over `[channels, length]` inputs, as lists of input indices.
+fn windows_1d(shape: &[usize], kernel_size: usize, stride: usize) -> (Vec<Vec<usize>>, Vec<usize>) {
+    assert_eq!(shape.len(), 2, "expected [channels, length]");
+    assert!(stride > 0 && kernel_size > 0);
+    let (channels, length) = (shape[0], shape[1]);
+    assert!(length >= kernel_size);
+    let out_length = (length - kernel_size) / stride + 1;
+
+    let mut windows: Vec<Vec<usize>> = Vec::with_capacity(channels * out_length);
+    for channel in 0..channels {
+        for position in 0..out_length {
+            let start = channel * length + position * stride;
+            windows.push((start..start + kernel_size).collect());
+        }
+    }
+    return (windows, vec![channels, out_length]);
+}
+
+// Windows over `[channels, height, width]` inputs, as lists of input indices.
+fn windows_2d(shape: &[usize], kernel_size: usize, stride: usize) -> (Vec<Vec<usize>>, Vec<usize>) {
+    assert_eq!(shape.len(), 3, "expected [channels, height, width]");
+    assert!(stride > 0 && kernel_size > 0);
+    let (channels, height, width) = (shape[0], shape[1], shape[2]);
+    assert!(height >= kernel_size && width >= kernel_size);
+    let out_height = (height - kernel_size) / stride + 1;
+    let out_width = (width - kernel_size) / stride + 1;
+
+    let mut windows: Vec<Vec<usize>> = Vec::with_capacity(channels * out_height * out_width);
+    for channel in 0..channels {
+        for row in 0..out_height {
+            for column in 0..out_width {
+                let mut window: Vec<usize> = Vec::with_capacity(kernel_size * kernel_size);
+                for kernel_row in 0..kernel_size {
+                    for kernel_column in 0..kernel_size {
+                        let y = row * stride + kernel_row;
+                        let x = column * stride + kernel_column;
+                        window.push((channel * height + y) * width + x);
+                    }
+                }
+                windows.push(window);
+            }
+        }
+    }
+    return (windows, vec![channels, out_height, out_width]);
+}
+
+fn gather(inputs: &[ValueRef], window: &[usize]) -> Vec<ValueRef> {
+    return window.iter().map(|index| inputs[*index].clone()).collect();
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PoolKind {
+    Max,
+    Avg,
+}
+
+// Reduces every window of the input to its maximum or mean. Built by `max_1d`,
+// `avg_1d` on `[channels, length]` inputs and `max_2d`, `avg_2d` on
+// `[channels, height, width]` inputs.
+pub struct Pool {
+    inputs: Vec<ValueRef>,
+    outputs: Vec<ValueRef>,
+    input_shape: Vec<usize>,
+    output_shape: Vec<usize>,
+    kind: PoolKind,
+}
+
+impl Pool {
+    pub fn max_1d(
+        inputs: &[ValueRef],
+        input_shape: Vec<usize>,
+        kernel_size: usize,
+        stride: usize,
+    ) -> Pool {
+        let (windows, output_shape) = windows_1d(&input_shape, kernel_size, stride);
+        return Pool::new(inputs, input_shape, windows, output_shape, PoolKind::Max);
+    }
+
+    pub fn avg_1d(
+        inputs: &[ValueRef],
+        input_shape: Vec<usize>,
+        kernel_size: usize,
+        stride: usize,
+    ) -> Pool {
+        let (windows, output_shape) = windows_1d(&input_shape, kernel_size, stride);
+        return Pool::new(inputs, input_shape, windows, output_shape, PoolKind::Avg);
+    }
+
+    pub fn max_2d(
+        inputs: &[ValueRef],
+        input_shape: Vec<usize>,
+        kernel_size: usize,
+        stride: usize,
+    ) -> Pool {
+        let (windows, output_shape) = windows_2d(&input_shape, kernel_size, stride);
+        return Pool::new(inputs, input_shape, windows, output_shape, PoolKind::Max);
+    }
+
+    pub fn avg_2d(
+        inputs: &[ValueRef],
+        input_shape: Vec<usize>,
+        kernel_size: usize,
+        stride: usize,
+    ) -> Pool {
+        let (windows, output_shape) = windows_2d(&input_shape, kernel_size, stride);
+        return Pool::new(inputs, input_shape, windows, output_shape, PoolKind::Avg);
+    }
+
+    fn new(
+        inputs: &[ValueRef],
+        input_shape: Vec<usize>,
+        windows: Vec<Vec<usize>>,
+        output_shape: Vec<usize>,
+        kind: PoolKind,
+    ) -> Pool {
+        assert_eq!(inputs.len(), input_shape.iter().product::<usize>());
+        // every window has the same size
+        let scale = Value::from(1.0 / windows[0].len() as f64);
+        let outputs: Vec<ValueRef> = windows
+            .iter()
+            .map(|window| {
+                let window = gather(inputs, window);
+                return match kind {
+                    PoolKind::Max => Engine::max(&window),
+                    PoolKind::Avg => Engine::mul(&Engine::sum(&window), &scale),
+                };
+            })
+            .collect();
+        return Pool {
+            inputs: inputs.to_vec(),
+            outputs,
+            input_shape,
+            output_shape,
+            kind,
+        };
+    }
+
+    pub fn kind(&self) -> PoolKind {
+        return self.kind;
+    }
+}
+
+impl Module for Pool {
+    fn inputs(&self) -> Vec<ValueRef> {
+        return self.inputs.clone();
+    }
+
+    fn outputs(&self) -> Vec<ValueRef> {
+        return self.outputs.clone();
+    }
+
+    fn parameters(&self) -> Vec<ValueRef> {
+        return vec![];
+    }
+
+    fn input_shape(&self) -> Vec<usize> {
+        return self.input_shape.clone();
+    }
+
+    fn output_shape(&self) -> Vec<usize> {
+        return self.output_shape.clone();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pooling() {
+        let values: Vec<f64> = (0..32).map(|x| x as f64).collect();
+        let inputs: Vec<ValueRef> = (0..32).map(|_x| Value::from(0.0)).collect();
+        for input in inputs.iter() {
+            input.borrow_mut().needs_grad = true;
+        }
+        let mut max = Pool::max_2d(&inputs, vec![2, 4, 4], 2, 2);
+        let mut avg = Pool::avg_2d(&inputs, vec![2, 4, 4], 3, 1);
+        assert_eq!(max.output_shape(), vec![2, 2, 2]);
+        assert_eq!(avg.output_shape(), vec![2, 2, 2]);
+        assert_eq!((max.kind(), avg.kind()), (PoolKind::Max, PoolKind::Avg));
+        assert_eq!(
+            max.forward(&values),
+            vec![5.0, 7.0, 13.0, 15.0, 21.0, 23.0, 29.0, 31.0]
+        );
+        assert_eq!(avg.forward(&values)[..2], [5.0, 6.0]);
+
+        let output = max.outputs()[1].clone();
+        output.borrow_mut().grad = 1.0;
+        output.borrow_mut().backward();
+        let grads: Vec<f64> = inputs.iter().map(|input| input.borrow().grad).collect();
+        assert_eq!(grads[7], 1.0);
+        assert_eq!(grads.iter().sum::<f64>(), 1.0);
+
+        let mut max = Pool::max_1d(&inputs[..12], vec![2, 6], 3, 3);
+        let mut avg = Pool::avg_1d(&inputs[..12], vec![2, 6], 2, 2);
+        let signal = [1.0, 5.0, 2.0, 0.0, -1.0, 3.0, 4.0, 4.0, 8.0, 9.0, 1.0, 2.0];
+        assert_eq!(max.forward(&signal), vec![5.0, 3.0, 8.0, 9.0]);
+        assert_eq!(avg.forward(&signal), vec![3.0, 1.0, 1.0, 4.0, 8.5, 1.5]);
+    }
+}
@@ -0,0 +1,47 @@
+use crate::engine::ValueRef;
+use crate::nn::Module;
+
+// Reinterprets its inputs under a new shape, the values pass through untouched.
+pub struct Reshape {
+    nodes: Vec<ValueRef>,
+    input_shape: Vec<usize>,
+    output_shape: Vec<usize>,
+}
+
+impl Reshape {
+    pub fn new(inputs: &[ValueRef], input_shape: Vec<usize>, output_shape: Vec<usize>) -> Reshape {
+        assert_eq!(inputs.len(), input_shape.iter().product::<usize>());
+        assert_eq!(inputs.len(), output_shape.iter().product::<usize>());
+        return Reshape {
+            nodes: inputs.to_vec(),
+            input_shape,
+            output_shape,
+        };
+    }
+
+    pub fn flatten(inputs: &[ValueRef], input_shape: Vec<usize>) -> Reshape {
+        return Reshape::new(inputs, input_shape, vec![inputs.len()]);
+    }
+}
+
+impl Module for Reshape {
+    fn inputs(&self) -> Vec<ValueRef> {
+        return self.nodes.clone();
+    }
+
+    fn outputs(&self) -> Vec<ValueRef> {
+        return self.nodes.clone();
+    }
+
+    fn parameters(&self) -> Vec<ValueRef> {
+        return vec![];
+    }
+
+    fn input_shape(&self) -> Vec<usize> {
+        return self.input_shape.clone();
+    }
+
+    fn output_shape(&self) -> Vec<usize> {
+        return self.output_shape.clone();
+    }
+}
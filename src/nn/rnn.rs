@@ -0,0 +1,440 @@
+use crate::engine::Engine;
+use crate::engine::Value;
+use crate::engine::ValueRef;
+use crate::nn::Linear;
+use crate::nn::Module;
+
+// Keeps the gates away from saturation.
+fn gate_bound(hidden_size: usize) -> f64 {
+    return 1.0 / (hidden_size as f64).sqrt();
+}
+
+fn concat(left: &[ValueRef], right: &[ValueRef]) -> Vec<ValueRef> {
+    return left.iter().chain(right.iter()).cloned().collect();
+}
+
+fn one_minus(node: &ValueRef) -> ValueRef {
+    return Engine::add(&Value::from(1.0), &Engine::inv(node));
+}
+
+// A single time step. The state starts with the hidden vector, cells with more
+// state (the LSTM cell state) append it.
+pub trait RecurrentCell {
+    fn input_size(&self) -> usize;
+
+    fn hidden_size(&self) -> usize;
+
+    fn state_size(&self) -> usize {
+        return self.hidden_size();
+    }
+
+    fn step(&self, input: &[ValueRef], state: &[ValueRef]) -> Vec<ValueRef>;
+
+    fn named_parameters(&self) -> Vec<(String, ValueRef)>;
+}
+
+// Elman network: h' = tanh(W [x, h] + b)
+pub struct RnnCell {
+    input_size: usize,
+    hidden_size: usize,
+    gate: Linear,
+}
+
+impl RnnCell {
+    pub fn new(input_size: usize, hidden_size: usize) -> RnnCell {
+        let bound = gate_bound(hidden_size);
+        return RnnCell {
+            input_size,
+            hidden_size,
+            gate: Linear::with_bound(input_size + hidden_size, hidden_size, bound),
+        };
+    }
+}
+
+impl RecurrentCell for RnnCell {
+    fn input_size(&self) -> usize {
+        return self.input_size;
+    }
+
+    fn hidden_size(&self) -> usize {
+        return self.hidden_size;
+    }
+
+    fn step(&self, input: &[ValueRef], state: &[ValueRef]) -> Vec<ValueRef> {
+        return self
+            .gate
+            .apply(&concat(input, state))
+            .iter()
+            .map(Engine::tanh)
+            .collect();
+    }
+
+    fn named_parameters(&self) -> Vec<(String, ValueRef)> {
+        return self.gate.named_parameters("hidden");
+    }
+}
+
+pub struct LstmCell {
+    input_size: usize,
+    hidden_size: usize,
+    input_gate: Linear,
+    forget_gate: Linear,
+    cell_gate: Linear,
+    output_gate: Linear,
+}
+
+impl LstmCell {
+    pub fn new(input_size: usize, hidden_size: usize) -> LstmCell {
+        let bound = gate_bound(hidden_size);
+        let size = input_size + hidden_size;
+        return LstmCell {
+            input_size,
+            hidden_size,
+            input_gate: Linear::with_bound(size, hidden_size, bound),
+            forget_gate: Linear::with_bound(size, hidden_size, bound),
+            cell_gate: Linear::with_bound(size, hidden_size, bound),
+            output_gate: Linear::with_bound(size, hidden_size, bound),
+        };
+    }
+}
+
+impl RecurrentCell for LstmCell {
+    fn input_size(&self) -> usize {
+        return self.input_size;
+    }
+
+    fn hidden_size(&self) -> usize {
+        return self.hidden_size;
+    }
+
+    fn state_size(&self) -> usize {
+        return 2 * self.hidden_size;
+    }
+
+    fn step(&self, input: &[ValueRef], state: &[ValueRef]) -> Vec<ValueRef> {
+        let (hidden, cell) = state.split_at(self.hidden_size);
+        let combined = concat(input, hidden);
+        let input_gate = self.input_gate.apply(&combined);
+        let forget_gate = self.forget_gate.apply(&combined);
+        let cell_gate = self.cell_gate.apply(&combined);
+        let output_gate = self.output_gate.apply(&combined);
+
+        let mut next_hidden: Vec<ValueRef> = Vec::with_capacity(self.hidden_size);
+        let mut next_cell: Vec<ValueRef> = Vec::with_capacity(self.hidden_size);
+        for index in 0..self.hidden_size {
+            let kept = Engine::mul(&Engine::sigmoid(&forget_gate[index]), &cell[index]);
+            let written = Engine::mul(
+                &Engine::sigmoid(&input_gate[index]),
+                &Engine::tanh(&cell_gate[index]),
+            );
+            let c = Engine::add(&kept, &written);
+            next_hidden.push(Engine::mul(
+                &Engine::sigmoid(&output_gate[index]),
+                &Engine::tanh(&c),
+            ));
+            next_cell.push(c);
+        }
+        return concat(&next_hidden, &next_cell);
+    }
+
+    fn named_parameters(&self) -> Vec<(String, ValueRef)> {
+        let mut named = self.input_gate.named_parameters("input_gate");
+        named.extend(self.forget_gate.named_parameters("forget_gate"));
+        named.extend(self.cell_gate.named_parameters("cell_gate"));
+        named.extend(self.output_gate.named_parameters("output_gate"));
+        return named;
+    }
+}
+
+// The candidate applies the reset gate to the recurrent part only:
+// n = tanh(W x + r * (U h + b_u)), h' = (1 - z) * n + z * h
+pub struct GruCell {
+    input_size: usize,
+    hidden_size: usize,
+    update_gate: Linear,
+    reset_gate: Linear,
+    candidate_input: Linear,
+    candidate_hidden: Linear,
+}
+
+impl GruCell {
+    pub fn new(input_size: usize, hidden_size: usize) -> GruCell {
+        let bound = gate_bound(hidden_size);
+        let size = input_size + hidden_size;
+        return GruCell {
+            input_size,
+            hidden_size,
+            update_gate: Linear::with_bound(size, hidden_size, bound),
+            reset_gate: Linear::with_bound(size, hidden_size, bound),
+            candidate_input: Linear::with_bound(input_size, hidden_size, bound),
+            candidate_hidden: Linear::with_bound(hidden_size, hidden_size, bound),
+        };
+    }
+}
+
+impl RecurrentCell for GruCell {
+    fn input_size(&self) -> usize {
+        return self.input_size;
+    }
+
+    fn hidden_size(&self) -> usize {
+        return self.hidden_size;
+    }
+
+    fn step(&self, input: &[ValueRef], state: &[ValueRef]) -> Vec<ValueRef> {
+        let combined = concat(input, state);
+        let update_gate = self.update_gate.apply(&combined);
+        let reset_gate = self.reset_gate.apply(&combined);
+        let candidate_input = self.candidate_input.apply(input);
+        let candidate_hidden = self.candidate_hidden.apply(state);
+
+        let mut next: Vec<ValueRef> = Vec::with_capacity(self.hidden_size);
+        for index in 0..self.hidden_size {
+            let z = Engine::sigmoid(&update_gate[index]);
+            let r = Engine::sigmoid(&reset_gate[index]);
+            let n = Engine::tanh(&Engine::add(
+                &candidate_input[index],
+                &Engine::mul(&r, &candidate_hidden[index]),
+            ));
+            next.push(Engine::add(
+                &Engine::mul(&one_minus(&z), &n),
+                &Engine::mul(&z, &state[index]),
+            ));
+        }
+        return next;
+    }
+
+    fn named_parameters(&self) -> Vec<(String, ValueRef)> {
+        let mut named = self.update_gate.named_parameters("update_gate");
+        named.extend(self.reset_gate.named_parameters("reset_gate"));
+        named.extend(self.candidate_input.named_parameters("candidate_input"));
+        named.extend(self.candidate_hidden.named_parameters("candidate_hidden"));
+        return named;
+    }
+}
+
+struct Unrolled {
+    inputs: Vec<Vec<ValueRef>>,
+    hidden: Vec<Vec<ValueRef>>,
+    // state after the last unrolled step, where the next step continues
+    state: Vec<ValueRef>,
+}
+
+// Unrolls a cell over a sequence, all time steps share the cell's parameters.
+// The graph only grows to the longest sequence seen so far, shorter sequences run
+// on its first steps. As a `Module` the recurrent layer exposes the length most
+// recently used, with inputs of shape
+// `[length, input_size]` and the hidden states of every step as
+// `[length, hidden_size]`. With a truncation of `k` steps the gradient flowing
+// back through the state is cut every `k` steps.
+pub struct Recurrent<C: RecurrentCell> {
+    cell: C,
+    truncation: Option<usize>,
+    unrolled: Unrolled,
+    length: usize,
+}
+
+impl<C: RecurrentCell> Recurrent<C> {
+    pub fn new(cell: C, truncation: Option<usize>) -> Recurrent<C> {
+        assert!(truncation != Some(0));
+        let state = (0..cell.state_size()).map(|_x| Value::from(0.0)).collect();
+        let mut recurrent = Recurrent {
+            cell,
+            truncation,
+            unrolled: Unrolled {
+                inputs: vec![],
+                hidden: vec![],
+                state,
+            },
+            length: 1,
+        };
+        recurrent.unroll(1);
+        return recurrent;
+    }
+
+    pub fn cell(&self) -> &C {
+        return &self.cell;
+    }
+
+    // Extends the graph to `length` steps if needed and makes them the current ones.
+    pub fn unroll(&mut self, length: usize) {
+        assert!(length > 0);
+        self.length = length;
+
+        let unrolled = &mut self.unrolled;
+        for step in unrolled.inputs.len()..length {
+            let input: Vec<ValueRef> = (0..self.cell.input_size())
+                .map(|_x| Value::from(0.0))
+                .collect();
+            let mut state = self.cell.step(&input, &unrolled.state);
+            unrolled
+                .hidden
+                .push(state[..self.cell.hidden_size()].to_vec());
+            unrolled.inputs.push(input);
+
+            if let Some(truncation) = self.truncation {
+                if (step + 1) % truncation == 0 {
+                    state = state.iter().map(Engine::detach).collect();
+                }
+            }
+            unrolled.state = state;
+        }
+    }
+
+    // Runs a whole sequence and returns the hidden state after every step.
+    pub fn forward_sequence(&mut self, sequence: &[Vec<f64>]) -> Vec<Vec<f64>> {
+        self.unroll(sequence.len());
+        let flat: Vec<f64> = sequence.iter().flatten().cloned().collect();
+        let outputs = self.forward(&flat);
+        return outputs
+            .chunks(self.cell.hidden_size())
+            .map(|chunk| chunk.to_vec())
+            .collect();
+    }
+
+    pub fn hidden_states(&self) -> Vec<Vec<ValueRef>> {
+        return self.unrolled.hidden[..self.length].to_vec();
+    }
+
+    pub fn last_hidden(&self) -> Vec<ValueRef> {
+        return self.unrolled.hidden[self.length - 1].clone();
+    }
+}
+
+impl<C: RecurrentCell> Module for Recurrent<C> {
+    fn inputs(&self) -> Vec<ValueRef> {
+        return self.unrolled.inputs[..self.length].concat();
+    }
+
+    fn outputs(&self) -> Vec<ValueRef> {
+        return self.unrolled.hidden[..self.length].concat();
+    }
+
+    fn parameters(&self) -> Vec<ValueRef> {
+        return self
+            .cell
+            .named_parameters()
+            .into_iter()
+            .map(|(_, parameter)| parameter)
+            .collect();
+    }
+
+    fn named_parameters(&self) -> Vec<(String, ValueRef)> {
+        return self.cell.named_parameters();
+    }
+
+    fn input_shape(&self) -> Vec<usize> {
+        return vec![self.length, self.cell.input_size()];
+    }
+
+    fn output_shape(&self) -> Vec<usize> {
+        return vec![self.length, self.cell.hidden_size()];
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::engine::randomize;
+
+    fn check_gradients<C: RecurrentCell>(recurrent: &mut Recurrent<C>, sequence: &[Vec<f64>]) {
+        recurrent.unroll(sequence.len());
+        let squares: Vec<ValueRef> = recurrent.outputs().iter().map(Engine::pow).collect();
+        let loss = Engine::sum(&squares);
+        recurrent.forward_sequence(sequence);
+        loss.borrow_mut().forward();
+        recurrent.zero_grad();
+        loss.borrow_mut().grad = 1.0;
+        loss.borrow_mut().backward();
+
+        let epsilon = 1e-6;
+        for parameter in recurrent.parameters().iter() {
+            let value = parameter.borrow().value;
+            parameter.borrow_mut().value = value + epsilon;
+            loss.borrow_mut().forward();
+            let plus = loss.borrow().value;
+            parameter.borrow_mut().value = value - epsilon;
+            loss.borrow_mut().forward();
+            let minus = loss.borrow().value;
+            parameter.borrow_mut().value = value;
+
+            let numeric = (plus - minus) / (2.0 * epsilon);
+            assert!((parameter.borrow().grad - numeric).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_cell_gradients() {
+        let sequence = vec![vec![0.5, -1.0], vec![0.1, 0.3], vec![-0.7, 0.9]];
+        check_gradients(&mut Recurrent::new(RnnCell::new(2, 3), None), &sequence);
+        check_gradients(&mut Recurrent::new(LstmCell::new(2, 3), None), &sequence);
+        check_gradients(&mut Recurrent::new(GruCell::new(2, 3), None), &sequence);
+
+        let lstm = Recurrent::new(LstmCell::new(2, 3), None);
+        assert_eq!(lstm.parameter_count(), 4 * 3 * (2 + 3 + 1));
+        assert_eq!(lstm.named_parameters()[6].0, "input_gate.1.bias");
+    }
+
+    #[test]
+    fn test_variable_length_and_truncation() {
+        let mut full = Recurrent::new(GruCell::new(1, 2), None);
+        let parameters = full.parameter_count();
+        let short = full.forward_sequence(&[vec![1.0], vec![0.5]]);
+        let long = full.forward_sequence(&[vec![1.0], vec![0.5], vec![-1.0], vec![0.2]]);
+        assert_eq!(short.len(), 2);
+        assert_eq!(long.len(), 4);
+        // the same weights produce the same prefix
+        assert_eq!(short[..], long[..2]);
+        assert_eq!(full.parameter_count(), parameters);
+        // shorter sequences run on the first steps of the longest graph
+        let fourth = full.last_hidden();
+        full.unroll(2);
+        let second = full.last_hidden();
+        full.unroll(4);
+        assert!(std::rc::Rc::ptr_eq(&full.hidden_states()[1][0], &second[0]));
+        assert!(std::rc::Rc::ptr_eq(&full.last_hidden()[0], &fourth[0]));
+
+        for (truncation, reaches_first_step) in [(None, true), (Some(2), false)] {
+            let mut recurrent = Recurrent::new(RnnCell::new(1, 2), truncation);
+            recurrent.unroll(4);
+            for input in recurrent.inputs().iter() {
+                input.borrow_mut().needs_grad = true;
+            }
+            recurrent.forward(&[1.0, -0.5, 0.25, 0.75]);
+            let last = Engine::sum(&recurrent.last_hidden());
+            last.borrow_mut().grad = 1.0;
+            last.borrow_mut().backward();
+
+            let inputs = recurrent.inputs();
+            assert_ne!(inputs[2].borrow().grad, 0.0);
+            assert_eq!(inputs[0].borrow().grad != 0.0, reaches_first_step);
+        }
+    }
+
+    #[test]
+    fn test_lstm_remembers_first_input() {
+        let mut lstm = Recurrent::new(LstmCell::new(1, 4), None);
+        randomize(&lstm.parameters(), 0.5, 5);
+        lstm.unroll(3);
+        let output = Engine::sum(&lstm.last_hidden());
+        let target = Value::from(0.0);
+        let loss = Engine::pow(&Engine::add(&output, &Engine::inv(&target)));
+        let samples = [(vec![1.0, 0.0, 0.0], 0.5), (vec![-1.0, 0.0, 0.0], -0.5)];
+        for _ in 0..300 {
+            for (sequence, label) in samples.iter() {
+                lstm.forward(sequence);
+                target.borrow_mut().value = *label;
+                loss.borrow_mut().forward();
+                lstm.zero_grad();
+                loss.borrow_mut().grad = 1.0;
+                loss.borrow_mut().backward();
+                lstm.update(0.1);
+            }
+        }
+        for (sequence, label) in samples.iter() {
+            lstm.forward(sequence);
+            output.borrow_mut().forward();
+            assert!((output.borrow().value - label).abs() < 0.05);
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use std::rc::Rc;
+
+use crate::engine::Value;
+use crate::engine::ValueRef;
+use crate::nn::Module;
+
+// Chains modules, each one built on the outputs of the previous one.
+//
+// let mut model = Sequential::new(vec![4]);
+// model.push(Layer::with_activation(8, &model.outputs(), Activation::ReLU));
+// model.push(Layer::with_activation(2, &model.outputs(), Activation::Softmax));
+pub struct Sequential {
+    inputs: Vec<ValueRef>,
+    input_shape: Vec<usize>,
+    modules: Vec<Box<dyn Module>>,
+}
+
+impl Sequential {
+    pub fn new(input_shape: Vec<usize>) -> Sequential {
+        let size = input_shape.iter().product();
+        let inputs: Vec<ValueRef> = (0..size).map(|_x| Value::from(0.0)).collect();
+        return Sequential {
+            inputs,
+            input_shape,
+            modules: vec![],
+        };
+    }
+
+    // Panics unless the module was built on the current outputs and expects their shape.
+    pub fn push<M: Module + 'static>(&mut self, module: M) {
+        let outputs = self.outputs();
+        let inputs = module.inputs();
+        assert!(
+            outputs.len() == inputs.len()
+                && outputs
+                    .iter()
+                    .zip(inputs.iter())
+                    .all(|(o, i)| Rc::ptr_eq(o, i)),
+            "module {} is not connected to the outputs of the previous module",
+            self.modules.len()
+        );
+        assert_eq!(
+            self.output_shape(),
+            module.input_shape(),
+            "module {} expects a different input shape",
+            self.modules.len()
+        );
+        self.modules.push(Box::new(module));
+    }
+
+    pub fn len(&self) -> usize {
+        return self.modules.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.modules.is_empty();
+    }
+}
+
+impl Module for Sequential {
+    fn inputs(&self) -> Vec<ValueRef> {
+        return self.inputs.clone();
+    }
+
+    fn outputs(&self) -> Vec<ValueRef> {
+        return match self.modules.last() {
+            Some(module) => module.outputs(),
+            None => self.inputs.clone(),
+        };
+    }
+
+    fn parameters(&self) -> Vec<ValueRef> {
+        let mut parameters: Vec<ValueRef> = vec![];
+        for module in self.modules.iter() {
+            parameters.extend(module.parameters());
+        }
+        return parameters;
+    }
+
+    fn named_parameters(&self) -> Vec<(String, ValueRef)> {
+        let mut named: Vec<(String, ValueRef)> = vec![];
+        for (index, module) in self.modules.iter().enumerate() {
+            for (name, parameter) in module.named_parameters() {
+                named.push((format!("{}.{}", index, name), parameter));
+            }
+        }
+        return named;
+    }
+
+    fn input_shape(&self) -> Vec<usize> {
+        return self.input_shape.clone();
+    }
+
+    fn output_shape(&self) -> Vec<usize> {
+        return match self.modules.last() {
+            Some(module) => module.output_shape(),
+            None => self.input_shape.clone(),
+        };
+    }
+
+    fn before_forward(&mut self) {
+        for module in self.modules.iter_mut() {
+            module.before_forward();
+        }
+    }
+
+    fn after_forward(&mut self) {
+        for module in self.modules.iter_mut() {
+            module.after_forward();
+        }
+    }
+
+    fn buffers(&self) -> Vec<f64> {
+        return self
+            .modules
+            .iter()
+            .flat_map(|module| module.buffers())
+            .collect();
+    }
+
+    fn set_buffers(&mut self, buffers: &[f64]) {
+        let mut offset = 0;
+        for module in self.modules.iter_mut() {
+            let count = module.buffers().len();
+            module.set_buffers(&buffers[offset..offset + count]);
+            offset += count;
+        }
+        assert_eq!(offset, buffers.len());
+    }
+
+    fn train(&mut self, training: bool) {
+        for module in self.modules.iter_mut() {
+            module.train(training);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::nn::Activation;
+    use crate::nn::ActivationLayer;
+    use crate::nn::Layer;
+    use crate::nn::Reshape;
+
+    #[test]
+    fn test_sequential() {
+        let mut model = Sequential::new(vec![2, 2]);
+        model.push(Reshape::new(&model.outputs(), vec![2, 2], vec![4]));
+        model.push(Layer::with_activation(
+            3,
+            &model.outputs(),
+            Activation::Identity,
+        ));
+        model.push(ActivationLayer::new(
+            &model.outputs(),
+            vec![3],
+            Activation::Softmax,
+        ));
+
+        assert_eq!(model.len(), 3);
+        assert_eq!(model.output_shape(), vec![3]);
+        assert_eq!(model.parameter_count(), 3 * 5);
+        assert_eq!(model.named_parameters()[0].0, "1.neurons.0.bias");
+
+        let outputs = model.forward(&[1.0, 2.0, 3.0, 4.0]);
+        assert!((outputs.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    #[should_panic(expected = "expects a different input shape")]
+    fn test_shape_mismatch() {
+        let mut model = Sequential::new(vec![2, 2]);
+        model.push(Layer::with_activation(
+            3,
+            &model.outputs(),
+            Activation::ReLU,
+        ));
+    }
+}
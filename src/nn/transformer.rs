@@ -0,0 +1,202 @@
+use crate::engine::Engine;
+use crate::engine::ValueRef;
+use crate::nn::LayerNorm;
+use crate::nn::Linear;
+use crate::nn::Module;
+use crate::nn::MultiHeadAttention;
+
+// Pre-norm transformer block over inputs of shape `[sequence_length, model_size]`:
+// x = x + attention(norm(x)), x = x + feed_forward(norm(x)), where the feed
+// forward network is applied to every position with shared weights.
+pub struct TransformerBlock {
+    inputs: Vec<ValueRef>,
+    outputs: Vec<ValueRef>,
+    attention_norm: LayerNorm,
+    attention: MultiHeadAttention,
+    feed_forward_norm: LayerNorm,
+    expand: Linear,
+    contract: Linear,
+    shape: Vec<usize>,
+}
+
+impl TransformerBlock {
+    pub fn new(
+        inputs: &[ValueRef],
+        sequence_length: usize,
+        heads: usize,
+        feed_forward_size: usize,
+        causal: bool,
+    ) -> TransformerBlock {
+        assert!(sequence_length > 0);
+        assert_eq!(inputs.len() % sequence_length, 0);
+        let model_size = inputs.len() / sequence_length;
+        let positions: Vec<&[ValueRef]> = inputs.chunks(model_size).collect();
+
+        let attention_norm = LayerNorm::unbound(model_size, 1e-5);
+        let normalized: Vec<ValueRef> = positions
+            .iter()
+            .flat_map(|position| attention_norm.apply(position))
+            .collect();
+        let attention = MultiHeadAttention::new(&normalized, sequence_length, heads, causal);
+        let attended: Vec<ValueRef> = inputs
+            .iter()
+            .zip(attention.outputs().iter())
+            .map(|(input, output)| Engine::add(input, output))
+            .collect();
+
+        let feed_forward_norm = LayerNorm::unbound(model_size, 1e-5);
+        let expand = Linear::new(model_size, feed_forward_size);
+        let contract = Linear::new(feed_forward_size, model_size);
+        let mut outputs: Vec<ValueRef> = Vec::with_capacity(inputs.len());
+        for position in attended.chunks(model_size) {
+            let hidden: Vec<ValueRef> = expand
+                .apply(&feed_forward_norm.apply(position))
+                .iter()
+                .map(Engine::gelu)
+                .collect();
+            let update = contract.apply(&hidden);
+            for (residual, update) in position.iter().zip(update.iter()) {
+                outputs.push(Engine::add(residual, update));
+            }
+        }
+
+        return TransformerBlock {
+            inputs: inputs.to_vec(),
+            outputs,
+            attention_norm,
+            attention,
+            feed_forward_norm,
+            expand,
+            contract,
+            shape: vec![sequence_length, model_size],
+        };
+    }
+}
+
+impl Module for TransformerBlock {
+    fn inputs(&self) -> Vec<ValueRef> {
+        return self.inputs.clone();
+    }
+
+    fn outputs(&self) -> Vec<ValueRef> {
+        return self.outputs.clone();
+    }
+
+    fn parameters(&self) -> Vec<ValueRef> {
+        return self
+            .named_parameters()
+            .into_iter()
+            .map(|(_, parameter)| parameter)
+            .collect();
+    }
+
+    fn named_parameters(&self) -> Vec<(String, ValueRef)> {
+        let mut named: Vec<(String, ValueRef)> = vec![];
+        let prefixed = |prefix: &str, parameters: Vec<(String, ValueRef)>| {
+            return parameters
+                .into_iter()
+                .map(|(name, parameter)| (format!("{}.{}", prefix, name), parameter))
+                .collect::<Vec<(String, ValueRef)>>();
+        };
+        named.extend(prefixed(
+            "attention_norm",
+            self.attention_norm.named_parameters(),
+        ));
+        named.extend(prefixed("attention", self.attention.named_parameters()));
+        named.extend(prefixed(
+            "feed_forward_norm",
+            self.feed_forward_norm.named_parameters(),
+        ));
+        named.extend(self.expand.named_parameters("feed_forward.expand"));
+        named.extend(self.contract.named_parameters("feed_forward.contract"));
+        return named;
+    }
+
+    fn input_shape(&self) -> Vec<usize> {
+        return self.shape.clone();
+    }
+
+    fn output_shape(&self) -> Vec<usize> {
+        return self.shape.clone();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::engine::randomize;
+    use crate::engine::Value;
+    use crate::nn::Embedding;
+    use crate::nn::Graph;
+
+    #[test]
+    fn test_tiny_language_model() {
+        let corpus = "abcdabcdabcdabcd";
+        let vocabulary: Vec<char> = "abcd".chars().collect();
+        let ids: Vec<usize> = corpus
+            .chars()
+            .map(|c| vocabulary.iter().position(|v| *v == c).unwrap())
+            .collect();
+        let (length, model_size) = (3, 4);
+
+        let mut graph = Graph::new();
+        graph.input("tokens", vec![length]);
+        graph.apply("embedding", "tokens", |inputs, _| {
+            Embedding::new(inputs, vocabulary.len(), model_size)
+        });
+        graph.apply("block", "embedding", |inputs, _| {
+            TransformerBlock::new(inputs, length, 2, 8, true)
+        });
+        graph.output("block");
+        let head = Linear::new(model_size, vocabulary.len());
+
+        // next character cross entropy, summed over the positions
+        let hidden = graph.tensor("block");
+        let targets: Vec<ValueRef> = (0..length).map(|_x| Value::from(0.0)).collect();
+        let mut losses: Vec<ValueRef> = vec![];
+        for (position, target) in targets.iter().enumerate() {
+            let logits = head.apply(&hidden[position * model_size..(position + 1) * model_size]);
+            let probability = Engine::select(target, &Engine::softmax(&logits));
+            losses.push(Engine::inv(&Engine::log(&probability)));
+        }
+        let loss = Engine::sum(&losses);
+        let weights: Vec<ValueRef> = graph
+            .named_parameters()
+            .into_iter()
+            .filter(|(name, _)| !name.contains("norm"))
+            .map(|(_, parameter)| parameter)
+            .collect();
+        randomize(&weights, 0.5, 7);
+        let mut parameters = graph.parameters();
+        parameters.extend(head.named_parameters("head").into_iter().map(|(_, p)| p));
+
+        let mut epoch_losses = vec![];
+        for _ in 0..40 {
+            let mut total = 0.0;
+            for start in 0..ids.len() - length {
+                let window: Vec<f64> = ids[start..start + length]
+                    .iter()
+                    .map(|id| *id as f64)
+                    .collect();
+                graph.forward(&window);
+                for (position, target) in targets.iter().enumerate() {
+                    target.borrow_mut().value = ids[start + position + 1] as f64;
+                }
+                loss.borrow_mut().forward();
+                total += loss.borrow().value;
+
+                for parameter in parameters.iter() {
+                    parameter.borrow_mut().zero_grad();
+                }
+                loss.borrow_mut().grad = 1.0;
+                loss.borrow_mut().backward();
+                for parameter in parameters.iter() {
+                    let grad = parameter.borrow().grad;
+                    parameter.borrow_mut().value -= 0.05 * grad;
+                }
+            }
+            epoch_losses.push(total);
+        }
+        assert!(epoch_losses[39] < 0.1 * epoch_losses[0]);
+    }
+}
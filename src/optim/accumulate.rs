@@ -0,0 +1,90 @@
+use crate::engine::ValueRef;
+
+// Runs forward and backward for `batch_size` samples and returns the mean loss.
+// `sample_loss(i)` prepares sample `i` (writes inputs and targets or builds a
+// graph) and returns its loss node. Every backward pass is seeded with
+// `1 / batch_size`, so after the call the parameters hold the gradient of the
+// mean loss on top of whatever they held before: call `zero_grad` once before
+// the batch and step the optimizer once after it.
+pub fn accumulate_gradients<F>(batch_size: usize, mut sample_loss: F) -> f64
+where
+    F: FnMut(usize) -> ValueRef,
+{
+    assert!(batch_size > 0);
+    let scale = 1.0 / batch_size as f64;
+    let mut total = 0.0;
+    for index in 0..batch_size {
+        let loss = sample_loss(index);
+        loss.borrow_mut().forward();
+        total += loss.borrow().value;
+        loss.borrow_mut().grad = scale;
+        loss.borrow_mut().backward();
+    }
+    return total * scale;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::engine::randomize;
+    use crate::engine::Engine;
+    use crate::engine::Value;
+    use crate::nn::Module;
+    use crate::nn::MLP;
+
+    fn squared_error(output: &ValueRef, target: &ValueRef) -> ValueRef {
+        return Engine::pow(&Engine::add(output, &Engine::inv(target)));
+    }
+
+    #[test]
+    fn test_batch_gradient_is_mean_of_samples() {
+        let mut mlp = MLP::new(vec![2, 3, 1], vec![false, true, false], 2);
+        randomize(&mlp.parameters(), 1.0, 3);
+        let target = Value::from(0.0);
+        let loss = squared_error(&mlp.outputs()[0], &target);
+        let samples = [([0.5, -1.0], 1.0), ([2.0, 0.3], -0.5), ([-0.7, 0.9], 0.25)];
+
+        // reference: one sample at a time
+        let mut expected = vec![0.0; mlp.parameters().len()];
+        let mut expected_loss = 0.0;
+        for (inputs, y) in samples.iter() {
+            mlp.zero_grad();
+            mlp.set(inputs.to_vec());
+            target.borrow_mut().value = *y;
+            loss.borrow_mut().forward();
+            expected_loss += loss.borrow().value / 3.0;
+            loss.borrow_mut().grad = 1.0;
+            loss.borrow_mut().backward();
+            for (sum, parameter) in expected.iter_mut().zip(mlp.parameters().iter()) {
+                *sum += parameter.borrow().grad / 3.0;
+            }
+        }
+
+        mlp.zero_grad();
+        let mean_loss = accumulate_gradients(samples.len(), |index| {
+            mlp.set(samples[index].0.to_vec());
+            target.borrow_mut().value = samples[index].1;
+            return loss.clone();
+        });
+        assert!((mean_loss - expected_loss).abs() < 1e-12);
+        for (sum, parameter) in expected.iter().zip(mlp.parameters().iter()) {
+            assert!((parameter.borrow().grad - sum).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_accumulate_over_fresh_graphs() {
+        // a new loss graph per sample over shared parameters
+        let weight = Value::from(1.5);
+        weight.borrow_mut().needs_grad = true;
+        let data = [(1.0, 2.0), (2.0, 1.0)];
+        let mean_loss = accumulate_gradients(data.len(), |index| {
+            let (x, y) = data[index];
+            let output = Engine::mul(&weight, &Value::from(x));
+            return squared_error(&output, &Value::from(y));
+        });
+        // d/dw mean((w x - y)^2) = mean(2 x (w x - y))
+        assert_eq!(mean_loss, (0.25 + 4.0) / 2.0);
+        assert_eq!(weight.borrow().grad, (2.0 * -0.5 + 4.0 * 2.0) / 2.0);
+    }
+}
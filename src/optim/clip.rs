@@ -0,0 +1,73 @@
+use crate::engine::ValueRef;
+
+// Clamps the gradient of every parameter to `[-limit, limit]`. Call it after
+// `backward` and before the update.
+pub fn clip_grad_value(parameters: &[ValueRef], limit: f64) {
+    assert!(limit >= 0.0);
+    for parameter in parameters.iter() {
+        let grad = { parameter.borrow().grad };
+        parameter.borrow_mut().grad = grad.clamp(-limit, limit);
+    }
+}
+
+// L2 norm of all gradients taken together.
+pub fn grad_norm(parameters: &[ValueRef]) -> f64 {
+    return parameters
+        .iter()
+        .map(|parameter| parameter.borrow().grad.powi(2))
+        .sum::<f64>()
+        .sqrt();
+}
+
+// Rescales all gradients so that their global L2 norm is at most `max_norm` and
+// returns the norm before clipping. A non-finite norm leaves the gradients as
+// they are, the caller can skip the step.
+pub fn clip_grad_norm(parameters: &[ValueRef], max_norm: f64) -> f64 {
+    assert!(max_norm > 0.0);
+    let norm = grad_norm(parameters);
+    if norm.is_finite() && norm > max_norm {
+        let scale = max_norm / norm;
+        for parameter in parameters.iter() {
+            parameter.borrow_mut().grad *= scale;
+        }
+    }
+    return norm;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::engine::Value;
+
+    fn with_grads(grads: &[f64]) -> Vec<ValueRef> {
+        return grads
+            .iter()
+            .map(|grad| {
+                let parameter = Value::from(0.0);
+                parameter.borrow_mut().grad = *grad;
+                parameter
+            })
+            .collect();
+    }
+
+    #[test]
+    fn test_clip_grad_value() {
+        let parameters = with_grads(&[-5.0, 0.5, 2.0]);
+        clip_grad_value(&parameters, 1.0);
+        let grads: Vec<f64> = parameters.iter().map(|p| p.borrow().grad).collect();
+        assert_eq!(grads, vec![-1.0, 0.5, 1.0]);
+    }
+
+    #[test]
+    fn test_clip_grad_norm() {
+        let parameters = with_grads(&[3.0, -4.0]);
+        assert_eq!(clip_grad_norm(&parameters, 1.0), 5.0);
+        assert!((parameters[0].borrow().grad - 0.6).abs() < 1e-12);
+        assert!((parameters[1].borrow().grad + 0.8).abs() < 1e-12);
+        assert!((grad_norm(&parameters) - 1.0).abs() < 1e-12);
+
+        // already within the bound, nothing changes
+        assert!((clip_grad_norm(&parameters, 2.0) - 1.0).abs() < 1e-12);
+        assert!((parameters[0].borrow().grad - 0.6).abs() < 1e-12);
+    }
+}
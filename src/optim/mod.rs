@@ -0,0 +1,26 @@
+mod accumulate;
+mod clip;
+mod optimizer;
+mod regularization;
+mod scheduler;
+
+pub use accumulate::accumulate_gradients;
+pub use clip::clip_grad_norm;
+pub use clip::clip_grad_value;
+pub use clip::grad_norm;
+pub use optimizer::Adam;
+pub use optimizer::Optimizer;
+pub use optimizer::ParameterGroup;
+pub use optimizer::SGD;
+pub use regularization::is_bias;
+pub use regularization::split_biases;
+pub use regularization::Regularization;
+pub use scheduler::Chain;
+pub use scheduler::CosineAnnealingWarmRestarts;
+pub use scheduler::ExponentialLR;
+pub use scheduler::LinearWarmup;
+pub use scheduler::OneCycle;
+pub use scheduler::ReduceOnPlateau;
+pub use scheduler::Scheduler;
+pub use scheduler::SequentialLR;
+pub use scheduler::StepLR;
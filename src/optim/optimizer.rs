@@ -0,0 +1,318 @@
+use crate::engine::ValueRef;
+use crate::nn::Module;
+use crate::optim::split_biases;
+
+// Parameters sharing the same optimizer settings.
+pub struct ParameterGroup {
+    pub parameters: Vec<ValueRef>,
+    pub weight_decay: f64,
+}
+
+impl ParameterGroup {
+    pub fn new(parameters: Vec<ValueRef>) -> ParameterGroup {
+        return ParameterGroup::with_weight_decay(parameters, 0.0);
+    }
+
+    pub fn with_weight_decay(parameters: Vec<ValueRef>, weight_decay: f64) -> ParameterGroup {
+        assert!(weight_decay >= 0.0);
+        return ParameterGroup {
+            parameters,
+            weight_decay,
+        };
+    }
+
+    // Weights of `module` with `weight_decay`, biases in a second group without it.
+    pub fn decay_weights(module: &dyn Module, weight_decay: f64) -> Vec<ParameterGroup> {
+        let (weights, biases) = split_biases(module);
+        return vec![
+            ParameterGroup::with_weight_decay(weights, weight_decay),
+            ParameterGroup::new(biases),
+        ];
+    }
+}
+
+// Updates parameters from their gradients. Weight decay is decoupled from the
+// gradient: every step shrinks a parameter by `learning_rate * weight_decay * w`.
+pub trait Optimizer {
+    fn groups(&self) -> &[ParameterGroup];
+
+    fn step(&mut self);
+
+    fn learning_rate(&self) -> f64;
+
+    // Called by training loops driven by a `Scheduler`.
+    fn set_learning_rate(&mut self, learning_rate: f64);
+
+    // Learning rate, step counters and moment buffers, for checkpoints. The length
+    // only depends on the configuration and the parameter groups.
+    fn state(&self) -> Vec<f64>;
+
+    fn set_state(&mut self, state: &[f64]);
+
+    fn zero_grad(&self) {
+        for group in self.groups().iter() {
+            for parameter in group.parameters.iter() {
+                parameter.borrow_mut().zero_grad();
+            }
+        }
+    }
+}
+
+fn flatten(buffers: &[Vec<f64>]) -> Vec<f64> {
+    return buffers.iter().flatten().cloned().collect();
+}
+
+fn unflatten(buffers: &mut [Vec<f64>], values: &[f64]) {
+    let mut offset = 0;
+    for buffer in buffers.iter_mut() {
+        let count = buffer.len();
+        buffer.copy_from_slice(&values[offset..offset + count]);
+        offset += count;
+    }
+    assert_eq!(offset, values.len());
+}
+
+fn decay(parameter: &ValueRef, learning_rate: f64, weight_decay: f64) {
+    if weight_decay > 0.0 {
+        parameter.borrow_mut().value *= 1.0 - learning_rate * weight_decay;
+    }
+}
+
+pub struct SGD {
+    groups: Vec<ParameterGroup>,
+    learning_rate: f64,
+    momentum: f64,
+    velocities: Vec<Vec<f64>>,
+}
+
+impl SGD {
+    pub fn new(groups: Vec<ParameterGroup>, learning_rate: f64) -> SGD {
+        return SGD::with_momentum(groups, learning_rate, 0.0);
+    }
+
+    pub fn with_momentum(groups: Vec<ParameterGroup>, learning_rate: f64, momentum: f64) -> SGD {
+        assert!((0.0..1.0).contains(&momentum));
+        let velocities = groups
+            .iter()
+            .map(|group| vec![0.0; group.parameters.len()])
+            .collect();
+        return SGD {
+            groups,
+            learning_rate,
+            momentum,
+            velocities,
+        };
+    }
+}
+
+impl Optimizer for SGD {
+    fn groups(&self) -> &[ParameterGroup] {
+        return &self.groups;
+    }
+
+    fn step(&mut self) {
+        for (group, velocities) in self.groups.iter().zip(self.velocities.iter_mut()) {
+            for (parameter, velocity) in group.parameters.iter().zip(velocities.iter_mut()) {
+                if !parameter.borrow().needs_grad {
+                    continue;
+                }
+                let grad = { parameter.borrow().grad };
+                *velocity = self.momentum * *velocity + grad;
+                decay(parameter, self.learning_rate, group.weight_decay);
+                parameter.borrow_mut().value -= self.learning_rate * *velocity;
+            }
+        }
+    }
+
+    fn learning_rate(&self) -> f64 {
+        return self.learning_rate;
+    }
+
+    fn set_learning_rate(&mut self, learning_rate: f64) {
+        self.learning_rate = learning_rate;
+    }
+
+    fn state(&self) -> Vec<f64> {
+        let mut state = vec![self.learning_rate];
+        state.extend(flatten(&self.velocities));
+        return state;
+    }
+
+    fn set_state(&mut self, state: &[f64]) {
+        assert_eq!(
+            state.len(),
+            self.state().len(),
+            "SGD state does not match the parameter groups"
+        );
+        self.learning_rate = state[0];
+        unflatten(&mut self.velocities, &state[1..]);
+    }
+}
+
+// Adam with decoupled weight decay (AdamW) when a group has a weight decay.
+pub struct Adam {
+    groups: Vec<ParameterGroup>,
+    learning_rate: f64,
+    beta1: f64,
+    beta2: f64,
+    epsilon: f64,
+    first_moments: Vec<Vec<f64>>,
+    second_moments: Vec<Vec<f64>>,
+    steps: usize,
+}
+
+impl Adam {
+    pub fn new(groups: Vec<ParameterGroup>, learning_rate: f64) -> Adam {
+        return Adam::with_config(groups, learning_rate, 0.9, 0.999, 1e-8);
+    }
+
+    pub fn with_config(
+        groups: Vec<ParameterGroup>,
+        learning_rate: f64,
+        beta1: f64,
+        beta2: f64,
+        epsilon: f64,
+    ) -> Adam {
+        assert!((0.0..1.0).contains(&beta1) && (0.0..1.0).contains(&beta2));
+        let moments: Vec<Vec<f64>> = groups
+            .iter()
+            .map(|group| vec![0.0; group.parameters.len()])
+            .collect();
+        return Adam {
+            groups,
+            learning_rate,
+            beta1,
+            beta2,
+            epsilon,
+            first_moments: moments.clone(),
+            second_moments: moments,
+            steps: 0,
+        };
+    }
+}
+
+impl Optimizer for Adam {
+    fn groups(&self) -> &[ParameterGroup] {
+        return &self.groups;
+    }
+
+    fn step(&mut self) {
+        self.steps += 1;
+        let first_correction = 1.0 - self.beta1.powi(self.steps as i32);
+        let second_correction = 1.0 - self.beta2.powi(self.steps as i32);
+
+        for (index, group) in self.groups.iter().enumerate() {
+            for (position, parameter) in group.parameters.iter().enumerate() {
+                if !parameter.borrow().needs_grad {
+                    continue;
+                }
+                let grad = { parameter.borrow().grad };
+                let first = &mut self.first_moments[index][position];
+                *first = self.beta1 * *first + (1.0 - self.beta1) * grad;
+                let second = &mut self.second_moments[index][position];
+                *second = self.beta2 * *second + (1.0 - self.beta2) * grad * grad;
+
+                let first = self.first_moments[index][position] / first_correction;
+                let second = self.second_moments[index][position] / second_correction;
+                decay(parameter, self.learning_rate, group.weight_decay);
+                parameter.borrow_mut().value -=
+                    self.learning_rate * first / (second.sqrt() + self.epsilon);
+            }
+        }
+    }
+
+    fn learning_rate(&self) -> f64 {
+        return self.learning_rate;
+    }
+
+    fn set_learning_rate(&mut self, learning_rate: f64) {
+        self.learning_rate = learning_rate;
+    }
+
+    fn state(&self) -> Vec<f64> {
+        let mut state = vec![self.learning_rate, self.steps as f64];
+        state.extend(flatten(&self.first_moments));
+        state.extend(flatten(&self.second_moments));
+        return state;
+    }
+
+    fn set_state(&mut self, state: &[f64]) {
+        assert_eq!(
+            state.len(),
+            self.state().len(),
+            "Adam state does not match the parameter groups"
+        );
+        let moments = (state.len() - 2) / 2;
+        self.learning_rate = state[0];
+        self.steps = state[1] as usize;
+        unflatten(&mut self.first_moments, &state[2..2 + moments]);
+        unflatten(&mut self.second_moments, &state[2 + moments..]);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::engine::Engine;
+    use crate::engine::Value;
+    use crate::nn::Activation;
+    use crate::nn::Neuron;
+    use crate::optim::Regularization;
+    use crate::optim::Scheduler;
+    use crate::optim::StepLR;
+
+    #[test]
+    fn test_decoupled_weight_decay_skips_biases() {
+        let inputs: Vec<ValueRef> = (0..2).map(|_x| Value::from(0.0)).collect();
+        let neuron = Neuron::with_activation(&inputs, Activation::Identity);
+        for parameter in neuron.parameters().iter() {
+            parameter.borrow_mut().value = 1.0;
+        }
+        let mut optimizer = SGD::new(ParameterGroup::decay_weights(&neuron, 0.5), 0.1);
+        // no gradients, so only the decay moves the weights
+        optimizer.zero_grad();
+        optimizer.step();
+        let values: Vec<f64> = neuron
+            .parameters()
+            .iter()
+            .map(|p| p.borrow().value)
+            .collect();
+        assert_eq!(values, vec![1.0, 0.95, 0.95]);
+    }
+
+    #[test]
+    fn test_adam_with_l1_and_scheduler() {
+        let weight = Value::from(3.0);
+        weight.borrow_mut().needs_grad = true;
+        let target = Value::from(1.0);
+        let error = Engine::pow(&Engine::add(&weight, &Engine::inv(&target)));
+        let loss = Engine::add(
+            &error,
+            &Regularization::L1(0.5).penalty(std::slice::from_ref(&weight)),
+        );
+
+        let mut optimizer = Adam::new(vec![ParameterGroup::new(vec![weight.clone()])], 0.1);
+        let mut scheduler = StepLR::new(100, 0.5);
+        for _ in 0..400 {
+            optimizer.set_learning_rate(scheduler.learning_rate(0.1));
+            loss.borrow_mut().forward();
+            optimizer.zero_grad();
+            loss.borrow_mut().grad = 1.0;
+            loss.borrow_mut().backward();
+            optimizer.step();
+            scheduler.step();
+        }
+        // the minimum of (w - 1)^2 + 0.5 |w| is at w = 0.75
+        assert!((weight.borrow().value - 0.75).abs() < 1e-2);
+        assert!((optimizer.learning_rate() - 0.0125).abs() < 1e-12);
+    }
+
+    #[test]
+    #[should_panic(expected = "Adam state does not match the parameter groups")]
+    fn test_adam_rejects_short_state() {
+        let weight = Value::from(1.0);
+        let mut optimizer = Adam::new(vec![ParameterGroup::new(vec![weight])], 0.1);
+        assert_eq!(optimizer.state().len(), 4);
+        optimizer.set_state(&[0.1]);
+    }
+}
@@ -0,0 +1,106 @@
+use crate::engine::Engine;
+use crate::engine::Value;
+use crate::engine::ValueRef;
+use crate::nn::Module;
+
+// Penalty terms to add to a loss. L2 is scaled by one half, so its gradient is
+// `strength * w` like weight decay with the same strength.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Regularization {
+    L1(f64),
+    L2(f64),
+    ElasticNet { l1: f64, l2: f64 },
+}
+
+impl Regularization {
+    pub fn penalty(&self, parameters: &[ValueRef]) -> ValueRef {
+        let (l1, l2) = match *self {
+            Regularization::L1(strength) => (strength, 0.0),
+            Regularization::L2(strength) => (0.0, strength),
+            Regularization::ElasticNet { l1, l2 } => (l1, l2),
+        };
+        assert!(l1 >= 0.0 && l2 >= 0.0);
+
+        // no terms without parameters either, e.g. for a module with only biases
+        let mut terms: Vec<ValueRef> = vec![];
+        if l1 > 0.0 && !parameters.is_empty() {
+            let absolute: Vec<ValueRef> = parameters.iter().map(Engine::abs).collect();
+            terms.push(Engine::mul(&Engine::sum(&absolute), &Value::from(l1)));
+        }
+        if l2 > 0.0 && !parameters.is_empty() {
+            let squares: Vec<ValueRef> = parameters.iter().map(Engine::pow).collect();
+            terms.push(Engine::mul(&Engine::sum(&squares), &Value::from(0.5 * l2)));
+        }
+        if terms.is_empty() {
+            return Value::from(0.0);
+        }
+        return Engine::sum(&terms);
+    }
+
+    // Penalty over the parameters of `module`, leaving out its biases.
+    pub fn module_penalty(&self, module: &dyn Module) -> ValueRef {
+        let (weights, _) = split_biases(module);
+        return self.penalty(&weights);
+    }
+}
+
+// Biases are named "bias" by every module, e.g. parameter 0 of a `Neuron`.
+pub fn is_bias(name: &str) -> bool {
+    return name.split('.').any(|part| part == "bias");
+}
+
+// Returns the parameters of `module` as (weights, biases).
+pub fn split_biases(module: &dyn Module) -> (Vec<ValueRef>, Vec<ValueRef>) {
+    let mut weights: Vec<ValueRef> = vec![];
+    let mut biases: Vec<ValueRef> = vec![];
+    for (name, parameter) in module.named_parameters() {
+        match is_bias(&name) {
+            true => biases.push(parameter),
+            false => weights.push(parameter),
+        }
+    }
+    return (weights, biases);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::nn::Activation;
+    use crate::nn::Neuron;
+
+    #[test]
+    fn test_elastic_net_penalty() {
+        let parameters = vec![Value::from(-2.0), Value::from(3.0)];
+        for parameter in parameters.iter() {
+            parameter.borrow_mut().needs_grad = true;
+        }
+        let penalty = Regularization::ElasticNet { l1: 0.1, l2: 0.2 }.penalty(&parameters);
+        // 0.1 * (2 + 3) + 0.5 * 0.2 * (4 + 9)
+        assert!((penalty.borrow().value - 1.8).abs() < 1e-12);
+
+        penalty.borrow_mut().grad = 1.0;
+        penalty.borrow_mut().backward();
+        assert!((parameters[0].borrow().grad - (-0.1 - 0.4)).abs() < 1e-12);
+        assert!((parameters[1].borrow().grad - (0.1 + 0.6)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_biases_are_excluded() {
+        let inputs: Vec<ValueRef> = (0..3).map(|_x| Value::from(1.0)).collect();
+        let neuron = Neuron::with_activation(&inputs, Activation::Identity);
+        let (weights, biases) = split_biases(&neuron);
+        assert_eq!(weights.len(), 3);
+        assert_eq!(biases.len(), 1);
+        assert!(std::rc::Rc::ptr_eq(&biases[0], &neuron.parameters()[0]));
+
+        for (index, parameter) in neuron.parameters().iter().enumerate() {
+            parameter.borrow_mut().value = index as f64;
+        }
+        let penalty = Regularization::L2(2.0).module_penalty(&neuron);
+        assert_eq!(penalty.borrow().value, 1.0 + 4.0 + 9.0);
+
+        let bias_only = Neuron::with_activation(&vec![], Activation::Identity);
+        let penalty = Regularization::ElasticNet { l1: 0.1, l2: 0.2 }.module_penalty(&bias_only);
+        assert_eq!(penalty.borrow().value, 0.0);
+    }
+}
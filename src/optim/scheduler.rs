@@ -0,0 +1,556 @@
+use std::f64::consts::PI;
+
+// A scheduler yields a multiplier for a base learning rate. Call `step` once per
+// step or epoch, whichever granularity the schedule was configured for.
+pub trait Scheduler {
+    fn factor(&self) -> f64;
+
+    fn step(&mut self);
+
+    // Schedulers that react to a monitored metric (e.g. validation loss) override
+    // this, everything else ignores the metric.
+    fn step_with_metric(&mut self, _metric: f64) {
+        self.step();
+    }
+
+    fn learning_rate(&self, base: f64) -> f64 {
+        return base * self.factor();
+    }
+
+    // Position in the schedule, for checkpoints.
+    fn state(&self) -> Vec<f64>;
+
+    fn set_state(&mut self, state: &[f64]);
+}
+
+pub struct StepLR {
+    step_size: usize,
+    gamma: f64,
+    epoch: usize,
+}
+
+impl StepLR {
+    pub fn new(step_size: usize, gamma: f64) -> StepLR {
+        assert!(step_size > 0);
+        return StepLR {
+            step_size,
+            gamma,
+            epoch: 0,
+        };
+    }
+}
+
+impl Scheduler for StepLR {
+    fn factor(&self) -> f64 {
+        return self.gamma.powi((self.epoch / self.step_size) as i32);
+    }
+
+    fn step(&mut self) {
+        self.epoch += 1;
+    }
+
+    fn state(&self) -> Vec<f64> {
+        return vec![self.epoch as f64];
+    }
+
+    fn set_state(&mut self, state: &[f64]) {
+        assert_eq!(state.len(), 1);
+        self.epoch = state[0] as usize;
+    }
+}
+
+pub struct ExponentialLR {
+    gamma: f64,
+    epoch: usize,
+}
+
+impl ExponentialLR {
+    pub fn new(gamma: f64) -> ExponentialLR {
+        return ExponentialLR { gamma, epoch: 0 };
+    }
+}
+
+impl Scheduler for ExponentialLR {
+    fn factor(&self) -> f64 {
+        return self.gamma.powi(self.epoch as i32);
+    }
+
+    fn step(&mut self) {
+        self.epoch += 1;
+    }
+
+    fn state(&self) -> Vec<f64> {
+        return vec![self.epoch as f64];
+    }
+
+    fn set_state(&mut self, state: &[f64]) {
+        assert_eq!(state.len(), 1);
+        self.epoch = state[0] as usize;
+    }
+}
+
+pub struct CosineAnnealingWarmRestarts {
+    period: usize,
+    period_multiplier: usize,
+    min_factor: f64,
+    position: usize,
+}
+
+impl CosineAnnealingWarmRestarts {
+    pub fn new(
+        period: usize,
+        period_multiplier: usize,
+        min_factor: f64,
+    ) -> CosineAnnealingWarmRestarts {
+        assert!(period > 0);
+        assert!(period_multiplier > 0);
+        return CosineAnnealingWarmRestarts {
+            period,
+            period_multiplier,
+            min_factor,
+            position: 0,
+        };
+    }
+}
+
+impl Scheduler for CosineAnnealingWarmRestarts {
+    fn factor(&self) -> f64 {
+        let progress = self.position as f64 / self.period as f64;
+        return self.min_factor + (1.0 - self.min_factor) * (1.0 + (PI * progress).cos()) / 2.0;
+    }
+
+    fn step(&mut self) {
+        self.position += 1;
+        if self.position >= self.period {
+            self.position = 0;
+            self.period *= self.period_multiplier;
+        }
+    }
+
+    fn state(&self) -> Vec<f64> {
+        return vec![self.position as f64, self.period as f64];
+    }
+
+    fn set_state(&mut self, state: &[f64]) {
+        assert_eq!(state.len(), 2);
+        self.position = state[0] as usize;
+        self.period = state[1] as usize;
+    }
+}
+
+pub struct LinearWarmup {
+    warmup_steps: usize,
+    start_factor: f64,
+    epoch: usize,
+}
+
+impl LinearWarmup {
+    pub fn new(warmup_steps: usize, start_factor: f64) -> LinearWarmup {
+        return LinearWarmup {
+            warmup_steps,
+            start_factor,
+            epoch: 0,
+        };
+    }
+}
+
+impl Scheduler for LinearWarmup {
+    fn factor(&self) -> f64 {
+        if self.epoch >= self.warmup_steps {
+            return 1.0;
+        }
+        let progress = self.epoch as f64 / self.warmup_steps as f64;
+        return self.start_factor + (1.0 - self.start_factor) * progress;
+    }
+
+    fn step(&mut self) {
+        self.epoch += 1;
+    }
+
+    fn state(&self) -> Vec<f64> {
+        return vec![self.epoch as f64];
+    }
+
+    fn set_state(&mut self, state: &[f64]) {
+        assert_eq!(state.len(), 1);
+        self.epoch = state[0] as usize;
+    }
+}
+
+// The base learning rate is the peak of the cycle.
+pub struct OneCycle {
+    total_steps: usize,
+    warmup_fraction: f64,
+    div_factor: f64,
+    final_div_factor: f64,
+    epoch: usize,
+}
+
+impl OneCycle {
+    pub fn new(total_steps: usize) -> OneCycle {
+        return OneCycle::with_config(total_steps, 0.3, 25.0, 1e4);
+    }
+
+    pub fn with_config(
+        total_steps: usize,
+        warmup_fraction: f64,
+        div_factor: f64,
+        final_div_factor: f64,
+    ) -> OneCycle {
+        assert!(total_steps > 1);
+        assert!(warmup_fraction > 0.0 && warmup_fraction < 1.0);
+        return OneCycle {
+            total_steps,
+            warmup_fraction,
+            div_factor,
+            final_div_factor,
+            epoch: 0,
+        };
+    }
+
+    fn anneal(start: f64, end: f64, progress: f64) -> f64 {
+        return end + (start - end) / 2.0 * (1.0 + (PI * progress).cos());
+    }
+}
+
+impl Scheduler for OneCycle {
+    fn factor(&self) -> f64 {
+        let initial = 1.0 / self.div_factor;
+        let last = initial / self.final_div_factor;
+        let peak = (self.warmup_fraction * self.total_steps as f64 - 1.0).max(1.0);
+        let epoch = self.epoch.min(self.total_steps - 1) as f64;
+
+        if epoch <= peak {
+            return OneCycle::anneal(initial, 1.0, epoch / peak);
+        }
+        let remaining = (self.total_steps - 1) as f64 - peak;
+        return OneCycle::anneal(1.0, last, (epoch - peak) / remaining);
+    }
+
+    fn step(&mut self) {
+        self.epoch += 1;
+    }
+
+    fn state(&self) -> Vec<f64> {
+        return vec![self.epoch as f64];
+    }
+
+    fn set_state(&mut self, state: &[f64]) {
+        assert_eq!(state.len(), 1);
+        self.epoch = state[0] as usize;
+    }
+}
+
+// Lowers the learning rate by `decay` once the metric passed to `step_with_metric`
+// has not improved (decreased) for more than `patience` consecutive calls.
+pub struct ReduceOnPlateau {
+    decay: f64,
+    patience: usize,
+    threshold: f64,
+    cooldown: usize,
+    min_factor: f64,
+    current: f64,
+    // None until the first metric
+    best: Option<f64>,
+    bad_epochs: usize,
+    cooldown_remaining: usize,
+}
+
+impl ReduceOnPlateau {
+    pub fn new(decay: f64, patience: usize) -> ReduceOnPlateau {
+        return ReduceOnPlateau::with_config(decay, patience, 1e-4, 0, 0.0);
+    }
+
+    pub fn with_config(
+        decay: f64,
+        patience: usize,
+        threshold: f64,
+        cooldown: usize,
+        min_factor: f64,
+    ) -> ReduceOnPlateau {
+        assert!(decay > 0.0 && decay < 1.0);
+        return ReduceOnPlateau {
+            decay,
+            patience,
+            threshold,
+            cooldown,
+            min_factor,
+            current: 1.0,
+            best: None,
+            bad_epochs: 0,
+            cooldown_remaining: 0,
+        };
+    }
+}
+
+impl Scheduler for ReduceOnPlateau {
+    fn factor(&self) -> f64 {
+        return self.current;
+    }
+
+    fn step(&mut self) {}
+
+    fn step_with_metric(&mut self, metric: f64) {
+        // relative to the magnitude, so it also holds for negative metrics
+        let improved = match self.best {
+            None => true,
+            Some(best) => metric < best - self.threshold * best.abs(),
+        };
+        if improved {
+            self.best = Some(metric);
+            self.bad_epochs = 0;
+        } else {
+            self.bad_epochs += 1;
+        }
+
+        if self.cooldown_remaining > 0 {
+            self.cooldown_remaining -= 1;
+            self.bad_epochs = 0;
+        }
+
+        if self.bad_epochs > self.patience {
+            self.current = (self.current * self.decay).max(self.min_factor);
+            self.cooldown_remaining = self.cooldown;
+            self.bad_epochs = 0;
+        }
+    }
+
+    fn state(&self) -> Vec<f64> {
+        return vec![
+            self.current,
+            self.best.is_some() as u8 as f64,
+            self.best.unwrap_or(0.0),
+            self.bad_epochs as f64,
+            self.cooldown_remaining as f64,
+        ];
+    }
+
+    fn set_state(&mut self, state: &[f64]) {
+        assert_eq!(state.len(), 5);
+        self.current = state[0];
+        self.best = match state[1] != 0.0 {
+            true => Some(state[2]),
+            false => None,
+        };
+        self.bad_epochs = state[3] as usize;
+        self.cooldown_remaining = state[4] as usize;
+    }
+}
+
+fn concat_states(schedulers: &[Box<dyn Scheduler>]) -> Vec<f64> {
+    return schedulers.iter().flat_map(|s| s.state()).collect();
+}
+
+// Every scheduler keeps a state of fixed length, so the current lengths split it.
+fn split_states(schedulers: &mut [Box<dyn Scheduler>], state: &[f64]) {
+    let mut offset = 0;
+    for scheduler in schedulers.iter_mut() {
+        let count = scheduler.state().len();
+        scheduler.set_state(&state[offset..offset + count]);
+        offset += count;
+    }
+    assert_eq!(offset, state.len());
+}
+
+// Applies several schedulers at once, multiplying their factors.
+pub struct Chain {
+    schedulers: Vec<Box<dyn Scheduler>>,
+}
+
+impl Chain {
+    pub fn new(schedulers: Vec<Box<dyn Scheduler>>) -> Chain {
+        return Chain { schedulers };
+    }
+}
+
+impl Scheduler for Chain {
+    fn factor(&self) -> f64 {
+        return self.schedulers.iter().map(|s| s.factor()).product();
+    }
+
+    fn step(&mut self) {
+        for scheduler in self.schedulers.iter_mut() {
+            scheduler.step();
+        }
+    }
+
+    fn step_with_metric(&mut self, metric: f64) {
+        for scheduler in self.schedulers.iter_mut() {
+            scheduler.step_with_metric(metric);
+        }
+    }
+
+    fn state(&self) -> Vec<f64> {
+        return concat_states(&self.schedulers);
+    }
+
+    fn set_state(&mut self, state: &[f64]) {
+        split_states(&mut self.schedulers, state);
+    }
+}
+
+// Hands over from one scheduler to the next at each milestone, every scheduler
+// starts from its own beginning once it becomes active.
+pub struct SequentialLR {
+    schedulers: Vec<Box<dyn Scheduler>>,
+    milestones: Vec<usize>,
+    epoch: usize,
+}
+
+impl SequentialLR {
+    pub fn new(schedulers: Vec<Box<dyn Scheduler>>, milestones: Vec<usize>) -> SequentialLR {
+        assert_eq!(schedulers.len(), milestones.len() + 1);
+        assert!(milestones.windows(2).all(|w| w[0] < w[1]));
+        return SequentialLR {
+            schedulers,
+            milestones,
+            epoch: 0,
+        };
+    }
+
+    fn active(&self) -> usize {
+        return self.milestones.iter().filter(|m| **m <= self.epoch).count();
+    }
+}
+
+impl Scheduler for SequentialLR {
+    fn factor(&self) -> f64 {
+        return self.schedulers[self.active()].factor();
+    }
+
+    fn step(&mut self) {
+        self.epoch += 1;
+        if !self.milestones.contains(&self.epoch) {
+            let active = self.active();
+            self.schedulers[active].step();
+        }
+    }
+
+    fn step_with_metric(&mut self, metric: f64) {
+        self.epoch += 1;
+        if !self.milestones.contains(&self.epoch) {
+            let active = self.active();
+            self.schedulers[active].step_with_metric(metric);
+        }
+    }
+
+    fn state(&self) -> Vec<f64> {
+        let mut state = vec![self.epoch as f64];
+        state.extend(concat_states(&self.schedulers));
+        return state;
+    }
+
+    fn set_state(&mut self, state: &[f64]) {
+        assert!(!state.is_empty());
+        self.epoch = state[0] as usize;
+        split_states(&mut self.schedulers, &state[1..]);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        return (a - b).abs() < 1e-9;
+    }
+
+    #[test]
+    fn test_step_and_exponential() {
+        let mut step = StepLR::new(2, 0.5);
+        let mut exponential = ExponentialLR::new(0.9);
+        let mut factors = vec![];
+        for _ in 0..5 {
+            factors.push((step.factor(), exponential.factor()));
+            step.step();
+            exponential.step();
+        }
+        assert!(close(factors[1].0, 1.0));
+        assert!(close(factors[2].0, 0.5));
+        assert!(close(factors[4].0, 0.25));
+        assert!(close(factors[3].1, 0.729));
+        assert!(close(step.learning_rate(0.1), 0.025));
+    }
+
+    #[test]
+    fn test_warmup_then_cosine_restarts() {
+        let mut scheduler = SequentialLR::new(
+            vec![
+                Box::new(LinearWarmup::new(4, 0.0)),
+                Box::new(CosineAnnealingWarmRestarts::new(2, 2, 0.0)),
+            ],
+            vec![4],
+        );
+        let mut factors = vec![];
+        for _ in 0..11 {
+            factors.push(scheduler.factor());
+            scheduler.step();
+        }
+        let expected = [0.0, 0.25, 0.5, 0.75, 1.0, 0.5, 1.0, 0.8535533905932737];
+        for (factor, expected) in factors.iter().zip(expected.iter()) {
+            assert!(close(*factor, *expected));
+        }
+        // the second cycle is twice as long, so the restart happens after four steps
+        assert!(close(factors[10], 1.0));
+    }
+
+    #[test]
+    fn test_one_cycle_and_plateau() {
+        let mut cycle = OneCycle::new(10);
+        let mut factors = vec![];
+        for _ in 0..10 {
+            factors.push(cycle.factor());
+            cycle.step();
+        }
+        assert!(close(factors[0], 1.0 / 25.0));
+        assert!(close(factors[2], 1.0));
+        assert!(close(factors[9], 1.0 / 25.0 / 1e4));
+
+        let mut plateau = ReduceOnPlateau::new(0.5, 1);
+        for metric in [1.0, 0.5, 0.5, 0.5, 0.5] {
+            plateau.step_with_metric(metric);
+        }
+        assert!(close(plateau.factor(), 0.5));
+
+        let mut chained = Chain::new(vec![Box::new(StepLR::new(1, 0.5)), Box::new(plateau)]);
+        chained.step_with_metric(0.5);
+        assert!(close(chained.factor(), 0.125));
+
+        let mut restored = Chain::new(vec![
+            Box::new(StepLR::new(1, 0.5)),
+            Box::new(ReduceOnPlateau::new(0.5, 1)),
+        ]);
+        restored.set_state(&chained.state());
+        assert!(close(restored.factor(), 0.125));
+    }
+
+    #[test]
+    fn test_plateau_with_negative_metrics() {
+        // a slightly worse negative metric is not an improvement
+        let mut plateau = ReduceOnPlateau::new(0.5, 0);
+        plateau.step_with_metric(-1.0);
+        plateau.step_with_metric(-0.99995);
+        assert!(close(plateau.factor(), 0.5));
+
+        let mut plateau = ReduceOnPlateau::new(0.5, 0);
+        for metric in [-1.0, -2.0, -3.0, 1e-3, -4.0] {
+            plateau.step_with_metric(metric);
+        }
+        assert!(close(plateau.factor(), 0.5));
+    }
+
+    #[test]
+    fn test_plateau_state_before_any_metric() {
+        let fresh = ReduceOnPlateau::new(0.5, 0);
+        assert!(fresh.state().iter().all(|value| value.is_finite()));
+
+        // a restored scheduler without a best metric takes the next one as the best
+        let mut used = ReduceOnPlateau::new(0.5, 0);
+        used.step_with_metric(-5.0);
+        used.set_state(&fresh.state());
+        used.step_with_metric(3.0);
+        assert_eq!(used.state(), vec![1.0, 1.0, 3.0, 0.0, 0.0]);
+        used.step_with_metric(3.0);
+        assert!(close(used.factor(), 0.5));
+    }
+}
@@ -0,0 +1,311 @@
+use std::fs;
+use std::io;
+use std::io::Cursor;
+use std::io::Read;
+use std::path::Path;
+
+use byteorder::LittleEndian;
+use byteorder::ReadBytesExt;
+use byteorder::WriteBytesExt;
+
+use crate::nn::Activation;
+use crate::nn::Module;
+use crate::nn::Normalization;
+use crate::nn::MLP;
+
+// Little-endian layout, version 1:
+//   magic "OXIDEMLP", version u32, input size u64, layer count u32,
+//   per layer: size u64, activation tag u8, activation argument f64,
+//   then once for the whole network: normalization tag u8,
+//   parameter count u64, parameters f64, buffer count u64, buffers f64,
+//   FNV-1a 64 checksum of all preceding bytes u64.
+// Parameters follow `Module::parameters` order.
+static MAGIC: &[u8; 8] = b"OXIDEMLP";
+const VERSION: u32 = 1;
+
+pub fn save_mlp(mlp: &MLP, path: &Path) -> io::Result<()> {
+    return fs::write(path, encode_mlp(mlp));
+}
+
+pub fn load_mlp(path: &Path) -> io::Result<MLP> {
+    return decode_mlp(&fs::read(path)?);
+}
+
+pub fn encode_mlp(mlp: &MLP) -> Vec<u8> {
+    let mut bytes: Vec<u8> = MAGIC.to_vec();
+    // writing into a Vec cannot fail
+    bytes.write_u32::<LittleEndian>(VERSION).unwrap();
+    bytes
+        .write_u64::<LittleEndian>(mlp.inputs().len() as u64)
+        .unwrap();
+
+    let sizes = mlp.sizes();
+    bytes.write_u32::<LittleEndian>(sizes.len() as u32).unwrap();
+    for (size, activation) in sizes.iter().zip(mlp.activations().iter()) {
+        let (tag, argument) = activation_tag(*activation);
+        bytes.write_u64::<LittleEndian>(*size as u64).unwrap();
+        bytes.write_u8(tag).unwrap();
+        bytes.write_f64::<LittleEndian>(argument).unwrap();
+    }
+    bytes
+        .write_u8(normalization_tag(mlp.normalization()))
+        .unwrap();
+
+    let parameters: Vec<f64> = mlp.parameters().iter().map(|p| p.borrow().value).collect();
+    for values in [parameters, mlp.buffers()] {
+        bytes
+            .write_u64::<LittleEndian>(values.len() as u64)
+            .unwrap();
+        for value in values {
+            bytes.write_f64::<LittleEndian>(value).unwrap();
+        }
+    }
+
+    let checksum = fnv1a(&bytes);
+    bytes.write_u64::<LittleEndian>(checksum).unwrap();
+    return bytes;
+}
+
+pub fn decode_mlp(bytes: &[u8]) -> io::Result<MLP> {
+    if bytes.len() < MAGIC.len() + 4 + 8 || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(invalid("not an MLP file"));
+    }
+    let (body, checksum) = bytes.split_at(bytes.len() - 8);
+    if fnv1a(body) != (&checksum[..]).read_u64::<LittleEndian>()? {
+        return Err(invalid("checksum mismatch"));
+    }
+
+    let mut reader = Cursor::new(&body[MAGIC.len()..]);
+    let version = reader.read_u32::<LittleEndian>()?;
+    if version != VERSION {
+        return Err(invalid(&format!("unsupported version {}", version)));
+    }
+    let input_size = reader.read_u64::<LittleEndian>()? as usize;
+    let layer_count = reader.read_u32::<LittleEndian>()? as usize;
+    let mut sizes: Vec<usize> = vec![];
+    let mut activations: Vec<Activation> = vec![];
+    for _ in 0..layer_count {
+        sizes.push(reader.read_u64::<LittleEndian>()? as usize);
+        let tag = reader.read_u8()?;
+        let argument = reader.read_f64::<LittleEndian>()?;
+        activations.push(activation_from_tag(tag, argument)?);
+    }
+    let normalization = normalization_from_tag(reader.read_u8()?)?;
+    if sizes.is_empty() || sizes.contains(&0) || input_size == 0 {
+        return Err(invalid("empty layer"));
+    }
+
+    let parameters = read_values(&mut reader)?;
+    let buffers = read_values(&mut reader)?;
+    if reader.position() as usize != body.len() - MAGIC.len() {
+        return Err(invalid("trailing bytes"));
+    }
+    // checked before building, the sizes are untrusted
+    if mlp_counts(input_size, &sizes, normalization) != Some((parameters.len(), buffers.len())) {
+        return Err(invalid("parameter count does not match the architecture"));
+    }
+
+    let mut mlp = MLP::with_normalization(sizes, activations, normalization, input_size);
+    let nodes = mlp.parameters();
+    for (node, value) in nodes.iter().zip(parameters.iter()) {
+        node.borrow_mut().value = *value;
+    }
+    mlp.set_buffers(&buffers);
+    return Ok(mlp);
+}
+
+// Parameter and buffer counts of an MLP, or None if they overflow.
+pub(super) fn mlp_counts(
+    input_size: usize,
+    sizes: &[usize],
+    normalization: Normalization,
+) -> Option<(usize, usize)> {
+    let mut parameters: usize = 0;
+    let mut buffers: usize = 0;
+    let mut inputs = input_size;
+    for size in sizes.iter() {
+        // a norm in front of every layer, see `MLP::with_normalization`
+        match normalization {
+            Normalization::None => {}
+            Normalization::Running => {
+                parameters = parameters.checked_add(inputs.checked_mul(2)?)?;
+                buffers = buffers.checked_add(inputs.checked_mul(2)?.checked_add(1)?)?;
+            }
+            Normalization::Layer => {
+                parameters = parameters.checked_add(inputs.checked_mul(2)?)?;
+            }
+        }
+        parameters = parameters.checked_add(size.checked_mul(inputs.checked_add(1)?)?)?;
+        inputs = *size;
+    }
+    return Some((parameters, buffers));
+}
+
+pub(super) fn read_values(reader: &mut Cursor<&[u8]>) -> io::Result<Vec<f64>> {
+    let count = reader.read_u64::<LittleEndian>()? as usize;
+    let remaining = reader.get_ref().len() - reader.position() as usize;
+    if count > remaining / 8 {
+        return Err(invalid("truncated values"));
+    }
+    let mut raw = vec![0u8; count * 8];
+    reader.read_exact(&mut raw)?;
+    return Ok(raw
+        .chunks(8)
+        .map(|mut chunk| chunk.read_f64::<LittleEndian>().unwrap())
+        .collect());
+}
+
+pub(super) fn invalid(message: &str) -> io::Error {
+    return io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+}
+
+pub(super) fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes.iter() {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    return hash;
+}
+
+fn activation_tag(activation: Activation) -> (u8, f64) {
+    return match activation {
+        Activation::Identity => (0, 0.0),
+        Activation::ReLU => (1, 0.0),
+        Activation::LeakyReLU(slope) => (2, slope),
+        Activation::ELU => (3, 0.0),
+        Activation::GELU => (4, 0.0),
+        Activation::SiLU => (5, 0.0),
+        Activation::Tanh => (6, 0.0),
+        Activation::Sigmoid => (7, 0.0),
+        Activation::Softplus => (8, 0.0),
+        Activation::Softmax => (9, 0.0),
+    };
+}
+
+fn activation_from_tag(tag: u8, argument: f64) -> io::Result<Activation> {
+    return match tag {
+        0 => Ok(Activation::Identity),
+        1 => Ok(Activation::ReLU),
+        2 => Ok(Activation::LeakyReLU(argument)),
+        3 => Ok(Activation::ELU),
+        4 => Ok(Activation::GELU),
+        5 => Ok(Activation::SiLU),
+        6 => Ok(Activation::Tanh),
+        7 => Ok(Activation::Sigmoid),
+        8 => Ok(Activation::Softplus),
+        9 => Ok(Activation::Softmax),
+        _ => Err(invalid(&format!("unknown activation {}", tag))),
+    };
+}
+
+fn normalization_tag(normalization: Normalization) -> u8 {
+    return match normalization {
+        Normalization::None => 0,
+        Normalization::Running => 1,
+        Normalization::Layer => 2,
+    };
+}
+
+fn normalization_from_tag(tag: u8) -> io::Result<Normalization> {
+    return match tag {
+        0 => Ok(Normalization::None),
+        1 => Ok(Normalization::Running),
+        2 => Ok(Normalization::Layer),
+        _ => Err(invalid(&format!("unknown normalization {}", tag))),
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::engine::randomize;
+    use rand::Rng;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    fn random_mlp(normalization: Normalization) -> MLP {
+        let mut mlp = MLP::with_normalization(
+            vec![5, 4, 3],
+            vec![
+                Activation::LeakyReLU(0.1),
+                Activation::Tanh,
+                Activation::Softmax,
+            ],
+            normalization,
+            6,
+        );
+        randomize(&mlp.parameters(), 1.0, 11);
+        // move the running statistics away from their defaults
+        let mut rng = ChaCha8Rng::seed_from_u64(12);
+        for _ in 0..3 {
+            let inputs: Vec<f64> = (0..6).map(|_x| rng.gen_range(-2.0..2.0)).collect();
+            mlp.forward(&inputs);
+        }
+        mlp.eval();
+        return mlp;
+    }
+
+    #[test]
+    fn test_round_trip_is_bit_exact() {
+        for normalization in [
+            Normalization::None,
+            Normalization::Running,
+            Normalization::Layer,
+        ] {
+            let mut mlp = random_mlp(normalization);
+            let path = std::env::temp_dir().join(format!("oxide_mlp_{:?}.bin", normalization));
+            save_mlp(&mlp, &path).unwrap();
+            let mut loaded = load_mlp(&path).unwrap();
+            fs::remove_file(path).unwrap();
+            loaded.eval();
+
+            assert_eq!(loaded.sizes(), mlp.sizes());
+            assert_eq!(loaded.activations(), mlp.activations());
+            assert_eq!(loaded.normalization(), normalization);
+            let inputs = [0.3, -1.2, 2.5, 0.0, 1e-3, -7.0];
+            let expected: Vec<u64> = mlp.forward(&inputs).iter().map(|v| v.to_bits()).collect();
+            let actual: Vec<u64> = loaded
+                .forward(&inputs)
+                .iter()
+                .map(|v| v.to_bits())
+                .collect();
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn test_rejects_corrupted_files() {
+        let bytes = encode_mlp(&random_mlp(Normalization::None));
+        assert!(decode_mlp(&bytes).is_ok());
+
+        let mut flipped = bytes.clone();
+        flipped[40] ^= 1;
+        assert!(decode_mlp(&flipped).is_err());
+        assert!(decode_mlp(&bytes[..bytes.len() - 1]).is_err());
+        assert!(decode_mlp(b"OXIDEMLP").is_err());
+    }
+
+    #[test]
+    fn test_rejects_huge_sizes() {
+        for normalization in [
+            Normalization::None,
+            Normalization::Running,
+            Normalization::Layer,
+        ] {
+            let mlp = random_mlp(normalization);
+            let (parameters, buffers) = mlp_counts(6, &mlp.sizes(), normalization).unwrap();
+            assert_eq!(parameters, mlp.parameters().len());
+            assert_eq!(buffers, mlp.buffers().len());
+
+            // a valid checksum over an absurd first layer size, at byte 8 + 4 + 8 + 4
+            let mut bytes = encode_mlp(&mlp);
+            bytes.truncate(bytes.len() - 8);
+            bytes[24..32].copy_from_slice(&(1u64 << 40).to_le_bytes());
+            let checksum = fnv1a(&bytes);
+            bytes.extend_from_slice(&checksum.to_le_bytes());
+            assert!(decode_mlp(&bytes).is_err());
+        }
+        assert_eq!(mlp_counts(usize::MAX, &[2], Normalization::None), None);
+    }
+}
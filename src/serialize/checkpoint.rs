@@ -0,0 +1,411 @@
+use std::fs;
+use std::io;
+use std::io::Cursor;
+use std::io::Read;
+use std::path::Path;
+use std::path::PathBuf;
+
+use byteorder::LittleEndian;
+use byteorder::ReadBytesExt;
+use byteorder::WriteBytesExt;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+use crate::engine::RngRef;
+use crate::nn::Module;
+use crate::optim::Optimizer;
+use crate::optim::Scheduler;
+use crate::serialize::binary::fnv1a;
+use crate::serialize::binary::invalid;
+use crate::serialize::binary::read_values;
+
+// Little-endian layout, version 1:
+//   magic "OXIDECKP", version u32, epoch u64, step u64,
+//   parameters, buffers, optimizer state and scheduler state, each as a
+//   count u64 followed by f64 values,
+//   RNG seed [u8; 32], stream u64, word position u128,
+//   FNV-1a 64 checksum of all preceding bytes u64.
+static MAGIC: &[u8; 8] = b"OXIDECKP";
+const VERSION: u32 = 1;
+
+// Everything needed to continue a training run exactly where it stopped.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Checkpoint {
+    pub epoch: u64,
+    pub step: u64,
+    pub parameters: Vec<f64>,
+    pub buffers: Vec<f64>,
+    pub optimizer: Vec<f64>,
+    pub scheduler: Vec<f64>,
+    pub rng_seed: [u8; 32],
+    pub rng_stream: u64,
+    pub rng_position: u128,
+}
+
+impl Checkpoint {
+    pub fn capture(
+        epoch: u64,
+        step: u64,
+        model: &dyn Module,
+        optimizer: &dyn Optimizer,
+        scheduler: &dyn Scheduler,
+        rng: &RngRef,
+    ) -> Checkpoint {
+        let rng = rng.borrow();
+        return Checkpoint {
+            epoch,
+            step,
+            parameters: model
+                .parameters()
+                .iter()
+                .map(|p| p.borrow().value)
+                .collect(),
+            buffers: model.buffers(),
+            optimizer: optimizer.state(),
+            scheduler: scheduler.state(),
+            rng_seed: rng.get_seed(),
+            rng_stream: rng.get_stream(),
+            rng_position: rng.get_word_pos(),
+        };
+    }
+
+    // The model, optimizer and scheduler must be built with the same configuration
+    // as the ones the checkpoint was captured from. Every length is checked before
+    // anything is written, so a mismatch leaves them untouched.
+    pub fn restore(
+        &self,
+        model: &mut dyn Module,
+        optimizer: &mut dyn Optimizer,
+        scheduler: &mut dyn Scheduler,
+        rng: &RngRef,
+    ) -> io::Result<()> {
+        let parameters = model.parameters();
+        for (name, expected, actual) in [
+            ("parameters", parameters.len(), self.parameters.len()),
+            ("buffers", model.buffers().len(), self.buffers.len()),
+            (
+                "optimizer state",
+                optimizer.state().len(),
+                self.optimizer.len(),
+            ),
+            (
+                "scheduler state",
+                scheduler.state().len(),
+                self.scheduler.len(),
+            ),
+        ] {
+            if expected != actual {
+                return Err(invalid(&format!(
+                    "checkpoint has {} {} values, expected {}",
+                    actual, name, expected
+                )));
+            }
+        }
+
+        for (parameter, value) in parameters.iter().zip(self.parameters.iter()) {
+            parameter.borrow_mut().value = *value;
+        }
+        model.set_buffers(&self.buffers);
+        optimizer.set_state(&self.optimizer);
+        scheduler.set_state(&self.scheduler);
+
+        let mut restored = ChaCha8Rng::from_seed(self.rng_seed);
+        restored.set_stream(self.rng_stream);
+        restored.set_word_pos(self.rng_position);
+        *rng.borrow_mut() = restored;
+        return Ok(());
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = MAGIC.to_vec();
+        // writing into a Vec cannot fail
+        bytes.write_u32::<LittleEndian>(VERSION).unwrap();
+        bytes.write_u64::<LittleEndian>(self.epoch).unwrap();
+        bytes.write_u64::<LittleEndian>(self.step).unwrap();
+        for values in [
+            &self.parameters,
+            &self.buffers,
+            &self.optimizer,
+            &self.scheduler,
+        ] {
+            bytes
+                .write_u64::<LittleEndian>(values.len() as u64)
+                .unwrap();
+            for value in values.iter() {
+                bytes.write_f64::<LittleEndian>(*value).unwrap();
+            }
+        }
+        bytes.extend_from_slice(&self.rng_seed);
+        bytes.write_u64::<LittleEndian>(self.rng_stream).unwrap();
+        bytes.write_u128::<LittleEndian>(self.rng_position).unwrap();
+
+        let checksum = fnv1a(&bytes);
+        bytes.write_u64::<LittleEndian>(checksum).unwrap();
+        return bytes;
+    }
+
+    pub fn decode(bytes: &[u8]) -> io::Result<Checkpoint> {
+        if bytes.len() < MAGIC.len() + 8 || &bytes[..MAGIC.len()] != MAGIC {
+            return Err(invalid("not a checkpoint file"));
+        }
+        let (body, checksum) = bytes.split_at(bytes.len() - 8);
+        if fnv1a(body) != (&checksum[..]).read_u64::<LittleEndian>()? {
+            return Err(invalid("checksum mismatch"));
+        }
+
+        let mut reader = Cursor::new(&body[MAGIC.len()..]);
+        let version = reader.read_u32::<LittleEndian>()?;
+        if version != VERSION {
+            return Err(invalid(&format!("unsupported version {}", version)));
+        }
+        let epoch = reader.read_u64::<LittleEndian>()?;
+        let step = reader.read_u64::<LittleEndian>()?;
+        let parameters = read_values(&mut reader)?;
+        let buffers = read_values(&mut reader)?;
+        let optimizer = read_values(&mut reader)?;
+        let scheduler = read_values(&mut reader)?;
+        let mut rng_seed = [0u8; 32];
+        reader.read_exact(&mut rng_seed)?;
+        let rng_stream = reader.read_u64::<LittleEndian>()?;
+        let rng_position = reader.read_u128::<LittleEndian>()?;
+        if reader.position() as usize != body.len() - MAGIC.len() {
+            return Err(invalid("trailing bytes"));
+        }
+
+        return Ok(Checkpoint {
+            epoch,
+            step,
+            parameters,
+            buffers,
+            optimizer,
+            scheduler,
+            rng_seed,
+            rng_stream,
+            rng_position,
+        });
+    }
+
+    // Writes to a temporary file first, so an interrupted save never leaves a
+    // truncated checkpoint behind.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let temporary = path.with_extension("tmp");
+        fs::write(&temporary, self.encode())?;
+        return fs::rename(&temporary, path);
+    }
+
+    pub fn load(path: &Path) -> io::Result<Checkpoint> {
+        return Checkpoint::decode(&fs::read(path)?);
+    }
+}
+
+// Saves checkpoints into a directory and keeps only the last `keep` of them.
+pub struct CheckpointManager {
+    directory: PathBuf,
+    keep: usize,
+}
+
+impl CheckpointManager {
+    pub fn new(directory: &Path, keep: usize) -> io::Result<CheckpointManager> {
+        assert!(keep > 0);
+        fs::create_dir_all(directory)?;
+        return Ok(CheckpointManager {
+            directory: PathBuf::from(directory),
+            keep,
+        });
+    }
+
+    pub fn save(&self, checkpoint: &Checkpoint) -> io::Result<PathBuf> {
+        let path = self
+            .directory
+            .join(format!("checkpoint-{:012}.ckpt", checkpoint.step));
+        checkpoint.save(&path)?;
+
+        let checkpoints = self.checkpoints()?;
+        if checkpoints.len() > self.keep {
+            for old in checkpoints[..checkpoints.len() - self.keep].iter() {
+                fs::remove_file(old)?;
+            }
+        }
+        return Ok(path);
+    }
+
+    // Checkpoint files ordered from oldest to newest step.
+    pub fn checkpoints(&self) -> io::Result<Vec<PathBuf>> {
+        let mut paths: Vec<PathBuf> = vec![];
+        for entry in fs::read_dir(&self.directory)? {
+            let path = entry?.path();
+            let name = path.file_name().unwrap().to_string_lossy().to_string();
+            if name.starts_with("checkpoint-") && name.ends_with(".ckpt") {
+                paths.push(path);
+            }
+        }
+        // the step is zero padded, so names sort by step
+        paths.sort();
+        return Ok(paths);
+    }
+
+    pub fn latest(&self) -> io::Result<Option<Checkpoint>> {
+        return match self.checkpoints()?.last() {
+            Some(path) => Ok(Some(Checkpoint::load(path)?)),
+            None => Ok(None),
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::engine::randomize;
+    use crate::engine::seeded_rng;
+    use crate::engine::Engine;
+    use crate::engine::Value;
+    use crate::engine::ValueRef;
+    use crate::nn::Activation;
+    use crate::nn::MLP;
+    use crate::optim::Adam;
+    use crate::optim::ParameterGroup;
+    use crate::optim::StepLR;
+    use rand::Rng;
+
+    struct Run {
+        mlp: MLP,
+        optimizer: Adam,
+        scheduler: StepLR,
+        rng: RngRef,
+        loss: ValueRef,
+        target: ValueRef,
+    }
+
+    fn new_run(seed: u64) -> Run {
+        let mlp =
+            MLP::with_activations(vec![3, 1], vec![Activation::Tanh, Activation::Identity], 2);
+        randomize(&mlp.parameters(), 1.0, seed);
+        let target = Value::from(0.0);
+        let error = Engine::add(&mlp.outputs()[0], &Engine::inv(&target));
+        let optimizer = Adam::new(vec![ParameterGroup::new(mlp.parameters())], 0.05);
+        return Run {
+            mlp,
+            optimizer,
+            scheduler: StepLR::new(3, 0.5),
+            rng: seeded_rng(seed),
+            loss: Engine::pow(&error),
+            target,
+        };
+    }
+
+    // One step on a sample drawn from the run's random stream.
+    fn train_step(run: &mut Run) {
+        let (x, y): (f64, f64) = {
+            let mut rng = run.rng.borrow_mut();
+            (rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0))
+        };
+        run.optimizer
+            .set_learning_rate(run.scheduler.learning_rate(0.05));
+        run.mlp.forward(&[x, y]);
+        run.target.borrow_mut().value = x * y;
+        run.loss.borrow_mut().forward();
+        run.optimizer.zero_grad();
+        run.loss.borrow_mut().grad = 1.0;
+        run.loss.borrow_mut().backward();
+        run.optimizer.step();
+        run.scheduler.step();
+    }
+
+    fn bits(mlp: &MLP) -> Vec<u64> {
+        return mlp
+            .parameters()
+            .iter()
+            .map(|p| p.borrow().value.to_bits())
+            .collect();
+    }
+
+    #[test]
+    fn test_resume_matches_uninterrupted_run() {
+        let mut uninterrupted = new_run(1);
+        for _ in 0..10 {
+            train_step(&mut uninterrupted);
+        }
+
+        let directory = std::env::temp_dir().join("oxide_checkpoint_resume");
+        let _ = fs::remove_dir_all(&directory);
+        let manager = CheckpointManager::new(&directory, 2).unwrap();
+        let mut interrupted = new_run(1);
+        for step in 1..=5 {
+            train_step(&mut interrupted);
+            let checkpoint = Checkpoint::capture(
+                0,
+                step,
+                &interrupted.mlp,
+                &interrupted.optimizer,
+                &interrupted.scheduler,
+                &interrupted.rng,
+            );
+            manager.save(&checkpoint).unwrap();
+        }
+        assert_eq!(manager.checkpoints().unwrap().len(), 2);
+
+        // a fresh process with different initial weights and random stream
+        let mut resumed = new_run(2);
+        let checkpoint = manager.latest().unwrap().unwrap();
+        assert_eq!(checkpoint.step, 5);
+        checkpoint
+            .restore(
+                &mut resumed.mlp,
+                &mut resumed.optimizer,
+                &mut resumed.scheduler,
+                &resumed.rng,
+            )
+            .unwrap();
+        for _ in 5..10 {
+            train_step(&mut resumed);
+        }
+        fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(bits(&resumed.mlp), bits(&uninterrupted.mlp));
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        let run = new_run(3);
+        let checkpoint =
+            Checkpoint::capture(4, 7, &run.mlp, &run.optimizer, &run.scheduler, &run.rng);
+        let mut bytes = checkpoint.encode();
+        assert_eq!(Checkpoint::decode(&bytes).unwrap(), checkpoint);
+        bytes[20] ^= 1;
+        assert!(Checkpoint::decode(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_restore_rejects_other_configurations() {
+        let run = new_run(3);
+        let checkpoint =
+            Checkpoint::capture(0, 1, &run.mlp, &run.optimizer, &run.scheduler, &run.rng);
+
+        let mut wider = new_run(4);
+        wider.mlp =
+            MLP::with_activations(vec![4, 1], vec![Activation::Tanh, Activation::Identity], 2);
+        let before = bits(&wider.mlp);
+        assert!(checkpoint
+            .restore(
+                &mut wider.mlp,
+                &mut wider.optimizer,
+                &mut wider.scheduler,
+                &wider.rng
+            )
+            .is_err());
+        assert_eq!(bits(&wider.mlp), before);
+
+        // matching model, but an optimizer over a single parameter
+        let mut other = new_run(4);
+        other.optimizer = Adam::new(vec![ParameterGroup::new(vec![Value::from(0.0)])], 0.05);
+        let before = bits(&other.mlp);
+        assert!(checkpoint
+            .restore(
+                &mut other.mlp,
+                &mut other.optimizer,
+                &mut other.scheduler,
+                &other.rng
+            )
+            .is_err());
+        assert_eq!(bits(&other.mlp), before);
+    }
+}
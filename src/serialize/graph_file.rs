@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::engine::Engine;
+use crate::engine::Operation;
+use crate::engine::Value;
+use crate::engine::ValueRef;
+use crate::serialize::binary::invalid;
+use crate::serialize::json_model::number;
+use crate::serialize::json_model::read_number;
+use crate::serialize::Json;
+
+// {
+//   "format": "oxide-graph", "version": 1,
+//   "nodes": [{"op": "none", "value": 0.5, "needs_grad": true},
+//             {"op": "pow", "argument": 2, "inputs": [0], "value": 0.25, "needs_grad": true}],
+//   "names": {"x": 0, "y": 1}
+// }
+// Nodes are listed after their inputs and refer to them by index. Every node
+// reachable from a named one is saved once, shared nodes stay shared.
+const FORMAT: &str = "oxide-graph";
+const VERSION: usize = 1;
+
+pub fn save_graph(named: &[(&str, &ValueRef)], path: &Path) -> io::Result<()> {
+    return fs::write(path, encode_graph(named));
+}
+
+pub fn load_graph(path: &Path) -> io::Result<Vec<(String, ValueRef)>> {
+    return decode_graph(&fs::read_to_string(path)?);
+}
+
+fn operation_name(operation: Operation) -> (&'static str, Option<f64>) {
+    return match operation {
+        Operation::ADD => ("add", None),
+        Operation::MUL => ("mul", None),
+        Operation::RELU => ("relu", None),
+        Operation::LEAKY_RELU(slope) => ("leaky_relu", Some(slope)),
+        Operation::ELU(alpha) => ("elu", Some(alpha)),
+        Operation::GELU => ("gelu", None),
+        Operation::EXP => ("exp", None),
+        Operation::LOG => ("log", None),
+        Operation::TANH => ("tanh", None),
+        Operation::SIGMOID => ("sigmoid", None),
+        Operation::SOFTPLUS => ("softplus", None),
+        Operation::POW(exponent) => ("pow", Some(exponent)),
+        Operation::MAX => ("max", None),
+        Operation::DETACH => ("detach", None),
+        Operation::SELECT => ("select", None),
+        Operation::NONE => ("none", None),
+    };
+}
+
+// Returns the operation and whether `inputs` is a valid number of inputs for it.
+fn operation_from_name(name: &str, argument: Option<f64>, inputs: usize) -> Option<Operation> {
+    let (operation, valid) = match (name, argument) {
+        ("add", None) => (Operation::ADD, inputs == 2),
+        ("mul", None) => (Operation::MUL, inputs == 2),
+        ("relu", None) => (Operation::RELU, inputs == 1),
+        ("leaky_relu", Some(slope)) => (Operation::LEAKY_RELU(slope), inputs == 1),
+        ("elu", Some(alpha)) => (Operation::ELU(alpha), inputs == 1),
+        ("gelu", None) => (Operation::GELU, inputs == 1),
+        ("exp", None) => (Operation::EXP, inputs == 1),
+        ("log", None) => (Operation::LOG, inputs == 1),
+        ("tanh", None) => (Operation::TANH, inputs == 1),
+        ("sigmoid", None) => (Operation::SIGMOID, inputs == 1),
+        ("softplus", None) => (Operation::SOFTPLUS, inputs == 1),
+        ("pow", Some(exponent)) => (Operation::POW(exponent), inputs == 1),
+        ("max", None) => (Operation::MAX, inputs >= 1),
+        ("detach", None) => (Operation::DETACH, inputs == 1),
+        ("select", None) => (Operation::SELECT, inputs >= 2),
+        ("none", None) => (Operation::NONE, inputs == 0),
+        _ => return None,
+    };
+    return match valid {
+        true => Some(operation),
+        false => None,
+    };
+}
+
+pub fn encode_graph(named: &[(&str, &ValueRef)]) -> String {
+    // iterative post-order, long chains such as `Engine::sum` would overflow the stack
+    let mut indices: HashMap<*const _, usize> = HashMap::new();
+    let mut order: Vec<ValueRef> = vec![];
+    for (_, root) in named.iter() {
+        let mut stack: Vec<(ValueRef, bool)> = vec![((*root).clone(), false)];
+        while let Some((node, expanded)) = stack.pop() {
+            let key = Rc::as_ptr(&node);
+            if indices.contains_key(&key) {
+                continue;
+            }
+            if expanded {
+                indices.insert(key, order.len());
+                order.push(node);
+                continue;
+            }
+            stack.push((node.clone(), true));
+            for previous in node.borrow().previous_nodes.iter().rev() {
+                if !indices.contains_key(&Rc::as_ptr(previous)) {
+                    stack.push((previous.clone(), false));
+                }
+            }
+        }
+    }
+
+    let entry = |key: &str, value: Json| (key.to_string(), value);
+    let nodes: Vec<Json> = order
+        .iter()
+        .map(|node| {
+            let node = node.borrow();
+            let (name, argument) = operation_name(node.operation);
+            let mut fields = vec![entry("op", Json::String(name.to_string()))];
+            if let Some(argument) = argument {
+                fields.push(entry("argument", number(argument)));
+            }
+            if !node.previous_nodes.is_empty() {
+                let inputs = node
+                    .previous_nodes
+                    .iter()
+                    .map(|previous| Json::Number(indices[&Rc::as_ptr(previous)] as f64))
+                    .collect();
+                fields.push(entry("inputs", Json::Array(inputs)));
+            }
+            fields.push(entry("value", number(node.value)));
+            fields.push(entry("needs_grad", Json::Bool(node.needs_grad)));
+            return Json::Object(fields);
+        })
+        .collect();
+    let names = named
+        .iter()
+        .map(|(name, node)| entry(name, Json::Number(indices[&Rc::as_ptr(node)] as f64)))
+        .collect();
+
+    let graph = Json::Object(vec![
+        entry("format", Json::String(FORMAT.to_string())),
+        entry("version", Json::Number(VERSION as f64)),
+        entry("nodes", Json::Array(nodes)),
+        entry("names", Json::Object(names)),
+    ]);
+    return graph.pretty() + "\n";
+}
+
+pub fn decode_graph(text: &str) -> io::Result<Vec<(String, ValueRef)>> {
+    let graph = Json::parse(text)?;
+    if graph.get("format").and_then(|f| f.as_str()) != Some(FORMAT) {
+        return Err(invalid("not an oxide-graph file"));
+    }
+    if graph.get("version").and_then(|v| v.as_usize()) != Some(VERSION) {
+        return Err(invalid("unsupported graph version"));
+    }
+    let entries = graph
+        .get("nodes")
+        .and_then(|nodes| nodes.as_array())
+        .ok_or_else(|| invalid("graph has no nodes"))?;
+
+    let mut nodes: Vec<ValueRef> = Vec::with_capacity(entries.len());
+    for (index, entry) in entries.iter().enumerate() {
+        let error = || invalid(&format!("graph node {} is invalid", index));
+        let name = entry
+            .get("op")
+            .and_then(|op| op.as_str())
+            .ok_or_else(error)?;
+        let argument = match entry.get("argument") {
+            Some(argument) => Some(read_number(argument)?),
+            None => None,
+        };
+        let mut inputs: Vec<ValueRef> = vec![];
+        if let Some(indices) = entry.get("inputs") {
+            for input in indices.as_array().ok_or_else(error)?.iter() {
+                // only earlier nodes, so the graph cannot contain cycles
+                let input = input.as_usize().filter(|i| *i < index).ok_or_else(error)?;
+                inputs.push(nodes[input].clone());
+            }
+        }
+        let operation = operation_from_name(name, argument, inputs.len()).ok_or_else(error)?;
+        let value = read_number(entry.get("value").ok_or_else(error)?)?;
+        let needs_grad = match entry.get("needs_grad") {
+            Some(Json::Bool(needs_grad)) => *needs_grad,
+            _ => return Err(error()),
+        };
+
+        // building a node computes it once, a select must find its option
+        if let Operation::SELECT = operation {
+            let position = inputs[0].borrow().value;
+            if !(position >= 0.0 && (position as usize) < inputs.len() - 1) {
+                return Err(invalid(&format!("graph node {} selects no input", index)));
+            }
+        }
+        let node = match operation {
+            Operation::NONE => Value::from(value),
+            _ => Engine::node(operation, inputs),
+        };
+        node.borrow_mut().value = value;
+        node.borrow_mut().needs_grad = needs_grad;
+        nodes.push(node);
+    }
+
+    let names = graph
+        .get("names")
+        .and_then(|names| names.as_object())
+        .ok_or_else(|| invalid("graph has no names"))?;
+    let mut named: Vec<(String, ValueRef)> = vec![];
+    for (name, index) in names.iter() {
+        let index = index
+            .as_usize()
+            .filter(|index| *index < nodes.len())
+            .ok_or_else(|| invalid(&format!("{} refers to no node", name)))?;
+        named.push((name.clone(), nodes[index].clone()));
+    }
+    return Ok(named);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn find(named: &[(String, ValueRef)], name: &str) -> ValueRef {
+        return named.iter().find(|(n, _)| n == name).unwrap().1.clone();
+    }
+
+    #[test]
+    fn test_round_trip_reexecutes() {
+        let x = Value::from(0.5);
+        x.borrow_mut().needs_grad = true;
+        let weights: Vec<ValueRef> = [0.3, -1.2, 2.0].iter().map(|w| Value::from(*w)).collect();
+        for weight in weights.iter() {
+            weight.borrow_mut().needs_grad = true;
+        }
+        let scaled: Vec<ValueRef> = weights.iter().map(|w| Engine::mul(w, &x)).collect();
+        let probabilities = Engine::softmax(&scaled);
+        let target = Value::from(2.0);
+        let picked = Engine::select(&target, &probabilities);
+        let loss = Engine::add(
+            &Engine::inv(&Engine::log(&picked)),
+            &Engine::leaky_relu(&Engine::detach(&Engine::tanh(&x)), 0.1),
+        );
+
+        let text = encode_graph(&[("x", &x), ("loss", &loss)]);
+        let named = decode_graph(&text).unwrap();
+        assert_eq!(
+            encode_graph(&[("x", &named[0].1), ("loss", &named[1].1)]),
+            text
+        );
+        // the softmax maximum is shared by all three exponentials
+        assert_eq!(text.matches("\"op\": \"max\"").count(), 1);
+
+        let (loaded_x, loaded_loss) = (find(&named, "x"), find(&named, "loss"));
+        for (input, loss) in [(&x, &loss), (&loaded_x, &loaded_loss)] {
+            input.borrow_mut().value = -1.5;
+            loss.borrow_mut().forward();
+            loss.borrow_mut().grad = 1.0;
+            loss.borrow_mut().backward();
+        }
+        assert_eq!(
+            loaded_loss.borrow().value.to_bits(),
+            loss.borrow().value.to_bits()
+        );
+        assert_ne!(x.borrow().grad, 0.0);
+        assert_eq!(loaded_x.borrow().grad.to_bits(), x.borrow().grad.to_bits());
+    }
+
+    #[test]
+    fn test_rejects_invalid_graphs() {
+        let cyclic = r#"{"format": "oxide-graph", "version": 1,
+            "nodes": [{"op": "relu", "inputs": [0], "value": 0, "needs_grad": true}],
+            "names": {"y": 0}}"#;
+        assert!(decode_graph(cyclic).is_err());
+        let arity = cyclic.replace("\"relu\", \"inputs\": [0]", "\"add\"");
+        assert!(decode_graph(&arity).is_err());
+
+        let select = r#"{"format": "oxide-graph", "version": 1,
+            "nodes": [{"op": "none", "value": 1, "needs_grad": false},
+                      {"op": "none", "value": 0.5, "needs_grad": true},
+                      {"op": "select", "inputs": [0, 1], "value": 0.5, "needs_grad": true}],
+            "names": {"y": 2}}"#;
+        assert!(decode_graph(select).is_err());
+        assert!(decode_graph(&select.replace("\"value\": 1,", "\"value\": 0,")).is_ok());
+    }
+}
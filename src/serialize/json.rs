@@ -0,0 +1,412 @@
+use std::fmt;
+use std::io;
+
+use crate::serialize::binary::invalid;
+
+// Minimal JSON document model. Objects keep their key order. Numbers are written
+// in Rust's shortest round-trip form (integers without a fraction), so every
+// finite f64 survives a round trip.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn parse(text: &str) -> io::Result<Json> {
+        let mut parser = Parser {
+            bytes: text.as_bytes(),
+            position: 0,
+        };
+        let value = parser.value(0)?;
+        parser.whitespace();
+        if parser.position != parser.bytes.len() {
+            return Err(parser.error("trailing characters"));
+        }
+        return Ok(value);
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        return match self {
+            Json::Object(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        };
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        return match self {
+            Json::Number(number) => Some(*number),
+            _ => None,
+        };
+    }
+
+    pub fn as_usize(&self) -> Option<usize> {
+        return match self {
+            Json::Number(number) if *number >= 0.0 && number.fract() == 0.0 => {
+                Some(*number as usize)
+            }
+            _ => None,
+        };
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        return match self {
+            Json::String(string) => Some(string),
+            _ => None,
+        };
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<Json>> {
+        return match self {
+            Json::Array(items) => Some(items),
+            _ => None,
+        };
+    }
+
+    pub fn as_object(&self) -> Option<&Vec<(String, Json)>> {
+        return match self {
+            Json::Object(entries) => Some(entries),
+            _ => None,
+        };
+    }
+
+    // Indented output for diffs: objects and nested arrays span several lines,
+    // arrays of scalars (e.g. a weight row) stay on one.
+    pub fn pretty(&self) -> String {
+        let mut out = String::new();
+        self.write_pretty(&mut out, 0);
+        return out;
+    }
+
+    fn write_pretty(&self, out: &mut String, indent: usize) {
+        let padding = " ".repeat(indent + 2);
+        match self {
+            Json::Array(items)
+                if items
+                    .iter()
+                    .any(|item| matches!(item, Json::Array(_) | Json::Object(_))) =>
+            {
+                out.push_str("[\n");
+                for (index, item) in items.iter().enumerate() {
+                    out.push_str(&padding);
+                    item.write_pretty(out, indent + 2);
+                    if index + 1 < items.len() {
+                        out.push(',');
+                    }
+                    out.push('\n');
+                }
+                out.push_str(&" ".repeat(indent));
+                out.push(']');
+            }
+            Json::Object(entries) if !entries.is_empty() => {
+                out.push_str("{\n");
+                for (index, (key, value)) in entries.iter().enumerate() {
+                    out.push_str(&padding);
+                    out.push_str(&Json::String(key.clone()).to_string());
+                    out.push_str(": ");
+                    value.write_pretty(out, indent + 2);
+                    if index + 1 < entries.len() {
+                        out.push(',');
+                    }
+                    out.push('\n');
+                }
+                out.push_str(&" ".repeat(indent));
+                out.push('}');
+            }
+            _ => out.push_str(&self.to_string()),
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter, string: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in string.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    return write!(f, "\"");
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Number(number) => {
+                assert!(number.is_finite(), "JSON cannot represent {}", number);
+                // integers without a fraction, so strict readers see shapes as integers
+                match number.fract() == 0.0
+                    && number.abs() < 1e15
+                    && number.to_bits() != (-0.0f64).to_bits()
+                {
+                    true => write!(f, "{}", *number as i64),
+                    false => write!(f, "{:?}", number),
+                }
+            }
+            Json::String(string) => write_string(f, string),
+            Json::Array(items) => {
+                write!(f, "[")?;
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(entries) => {
+                write!(f, "{{")?;
+                for (index, (key, value)) in entries.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        };
+    }
+}
+
+// Deeply nested documents are rejected instead of overflowing the stack.
+const MAX_DEPTH: usize = 128;
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &str) -> io::Error {
+        return invalid(&format!("JSON: {} at byte {}", message, self.position));
+    }
+
+    fn whitespace(&mut self) {
+        while self.position < self.bytes.len()
+            && matches!(self.bytes[self.position], b' ' | b'\t' | b'\n' | b'\r')
+        {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        return self.bytes.get(self.position).copied();
+    }
+
+    fn expect(&mut self, byte: u8) -> io::Result<()> {
+        self.whitespace();
+        if self.peek() != Some(byte) {
+            return Err(self.error(&format!("expected '{}'", byte as char)));
+        }
+        self.position += 1;
+        return Ok(());
+    }
+
+    fn literal(&mut self, literal: &str, value: Json) -> io::Result<Json> {
+        if !self.bytes[self.position..].starts_with(literal.as_bytes()) {
+            return Err(self.error("unknown literal"));
+        }
+        self.position += literal.len();
+        return Ok(value);
+    }
+
+    fn value(&mut self, depth: usize) -> io::Result<Json> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("nested too deeply"));
+        }
+        self.whitespace();
+        return match self.peek() {
+            Some(b'n') => self.literal("null", Json::Null),
+            Some(b't') => self.literal("true", Json::Bool(true)),
+            Some(b'f') => self.literal("false", Json::Bool(false)),
+            Some(b'"') => Ok(Json::String(self.string()?)),
+            Some(b'[') => {
+                self.position += 1;
+                let mut items: Vec<Json> = vec![];
+                self.whitespace();
+                if self.peek() == Some(b']') {
+                    self.position += 1;
+                    return Ok(Json::Array(items));
+                }
+                loop {
+                    items.push(self.value(depth + 1)?);
+                    self.whitespace();
+                    match self.peek() {
+                        Some(b',') => self.position += 1,
+                        Some(b']') => {
+                            self.position += 1;
+                            return Ok(Json::Array(items));
+                        }
+                        _ => return Err(self.error("expected ',' or ']'")),
+                    }
+                }
+            }
+            Some(b'{') => {
+                self.position += 1;
+                let mut entries: Vec<(String, Json)> = vec![];
+                self.whitespace();
+                if self.peek() == Some(b'}') {
+                    self.position += 1;
+                    return Ok(Json::Object(entries));
+                }
+                loop {
+                    self.whitespace();
+                    let key = self.string()?;
+                    self.expect(b':')?;
+                    entries.push((key, self.value(depth + 1)?));
+                    self.whitespace();
+                    match self.peek() {
+                        Some(b',') => self.position += 1,
+                        Some(b'}') => {
+                            self.position += 1;
+                            return Ok(Json::Object(entries));
+                        }
+                        _ => return Err(self.error("expected ',' or '}'")),
+                    }
+                }
+            }
+            Some(b'-') | Some(b'0'..=b'9') => self.number(),
+            _ => Err(self.error("unexpected character")),
+        };
+    }
+
+    fn number(&mut self) -> io::Result<Json> {
+        let start = self.position;
+        while let Some(byte) = self.peek() {
+            if !matches!(byte, b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') {
+                break;
+            }
+            self.position += 1;
+        }
+        // the characters are ASCII, so this cannot split a code point
+        let text = std::str::from_utf8(&self.bytes[start..self.position]).unwrap();
+        return match text.parse::<f64>() {
+            Ok(number) if number.is_finite() => Ok(Json::Number(number)),
+            _ => Err(self.error("invalid number")),
+        };
+    }
+
+    fn hex4(&mut self) -> io::Result<u32> {
+        let digits = self
+            .bytes
+            .get(self.position..self.position + 4)
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok());
+        return match digits {
+            Some(code) => {
+                self.position += 4;
+                Ok(code)
+            }
+            None => Err(self.error("invalid unicode escape")),
+        };
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        if self.peek() != Some(b'"') {
+            return Err(self.error("expected string"));
+        }
+        self.position += 1;
+        let mut bytes: Vec<u8> = vec![];
+        loop {
+            let byte = match self.peek() {
+                Some(byte) => byte,
+                None => return Err(self.error("unterminated string")),
+            };
+            self.position += 1;
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let escaped = self
+                        .peek()
+                        .ok_or_else(|| self.error("unterminated string"))?;
+                    self.position += 1;
+                    let c = match escaped {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let mut code = self.hex4()?;
+                            if (0xd800..0xdc00).contains(&code)
+                                && self.bytes[self.position..].starts_with(b"\\u")
+                            {
+                                self.position += 2;
+                                let low = self.hex4()?;
+                                if !(0xdc00..0xe000).contains(&low) {
+                                    return Err(self.error("invalid surrogate pair"));
+                                }
+                                code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                            }
+                            char::from_u32(code).ok_or_else(|| self.error("invalid code point"))?
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    let mut buffer = [0u8; 4];
+                    bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+                }
+                _ => bytes.push(byte),
+            }
+        }
+        return String::from_utf8(bytes).map_err(|_| self.error("invalid UTF-8"));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let text = r#"{"a":[1.0,-2.5e-300,0.1,null,true],"b\n\"":{"c":"é😀"}}"#;
+        let value = Json::parse(text).unwrap();
+        assert_eq!(
+            value.get("a").unwrap().as_array().unwrap()[2].as_f64(),
+            Some(0.1)
+        );
+        assert_eq!(
+            value.get("b\n\"").unwrap().get("c").unwrap().as_str(),
+            Some("é😀")
+        );
+        assert_eq!(Json::parse(&value.to_string()).unwrap(), value);
+
+        assert_eq!(
+            Json::parse("[3.0,-0.0,1e20]").unwrap().to_string(),
+            "[3,-0.0,1e20]"
+        );
+        for number in [
+            0.1 + 0.2,
+            -0.0,
+            f64::MIN_POSITIVE,
+            5e-324,
+            f64::MAX,
+            -1.0 / 3.0,
+        ] {
+            let parsed = Json::parse(&Json::Number(number).to_string()).unwrap();
+            assert_eq!(parsed.as_f64().unwrap().to_bits(), number.to_bits());
+        }
+        assert_eq!(
+            Json::parse(&value.pretty()).unwrap(),
+            value,
+            "{}",
+            value.pretty()
+        );
+        assert!(Json::parse("[1,]").is_err());
+        assert!(Json::parse("{\"a\":1} x").is_err());
+    }
+}
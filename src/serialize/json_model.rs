@@ -0,0 +1,348 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::nn::Activation;
+use crate::nn::Module;
+use crate::nn::Normalization;
+use crate::nn::MLP;
+use crate::serialize::assign_mlp_tensors;
+use crate::serialize::binary::invalid;
+use crate::serialize::binary::mlp_counts;
+use crate::serialize::mlp_buffers;
+use crate::serialize::mlp_tensors;
+use crate::serialize::Json;
+
+// {
+//   "format": "oxide-mlp", "version": 1, "input_size": 4, "normalization": "none",
+//   "layers": [{"size": 3, "activation": "relu", "weight": [[..], ..], "bias": [..]}],
+//   "norms": [{"weight": [..], "bias": [..]}], "buffers": [..]
+// }
+// Weights are one row per output. Non-finite values are written as the strings
+// "NaN", "Infinity" and "-Infinity", so a diverged model can still be inspected.
+const FORMAT: &str = "oxide-mlp";
+const VERSION: usize = 1;
+
+pub fn save_json_model(mlp: &MLP, path: &Path) -> io::Result<()> {
+    return fs::write(path, encode_json_model(mlp));
+}
+
+pub fn load_json_model(path: &Path) -> io::Result<MLP> {
+    return decode_json_model(&fs::read_to_string(path)?);
+}
+
+pub(super) fn number(value: f64) -> Json {
+    return match value {
+        v if v.is_finite() => Json::Number(v),
+        v if v.is_nan() => Json::String("NaN".to_string()),
+        v if v > 0.0 => Json::String("Infinity".to_string()),
+        _ => Json::String("-Infinity".to_string()),
+    };
+}
+
+fn numbers(values: &[f64]) -> Json {
+    return Json::Array(values.iter().map(|v| number(*v)).collect());
+}
+
+fn activation_name(activation: Activation) -> &'static str {
+    return match activation {
+        Activation::Identity => "identity",
+        Activation::ReLU => "relu",
+        Activation::LeakyReLU(_) => "leaky_relu",
+        Activation::ELU => "elu",
+        Activation::GELU => "gelu",
+        Activation::SiLU => "silu",
+        Activation::Tanh => "tanh",
+        Activation::Sigmoid => "sigmoid",
+        Activation::Softplus => "softplus",
+        Activation::Softmax => "softmax",
+    };
+}
+
+fn normalization_name(normalization: Normalization) -> &'static str {
+    return match normalization {
+        Normalization::None => "none",
+        Normalization::Running => "running",
+        Normalization::Layer => "layer",
+    };
+}
+
+pub fn encode_json_model(mlp: &MLP) -> String {
+    let tensors = mlp_tensors(mlp);
+    let values = |index: usize| -> Vec<f64> {
+        return tensors[index]
+            .nodes
+            .iter()
+            .map(|n| n.borrow().value)
+            .collect();
+    };
+    let entry = |key: &str, value: Json| (key.to_string(), value);
+
+    let mut layers: Vec<Json> = vec![];
+    for (index, activation) in mlp.activations().iter().enumerate() {
+        let columns = tensors[2 * index].shape[1];
+        let weight = values(2 * index);
+        let rows = weight.chunks(columns.max(1)).map(numbers).collect();
+        let mut layer = vec![
+            entry("size", Json::Number(mlp.sizes()[index] as f64)),
+            entry(
+                "activation",
+                Json::String(activation_name(*activation).to_string()),
+            ),
+        ];
+        if let Activation::LeakyReLU(slope) = activation {
+            layer.push(entry("slope", number(*slope)));
+        }
+        layer.push(entry("weight", Json::Array(rows)));
+        layer.push(entry("bias", numbers(&values(2 * index + 1))));
+        layers.push(Json::Object(layer));
+    }
+
+    let mut model = vec![
+        entry("format", Json::String(FORMAT.to_string())),
+        entry("version", Json::Number(VERSION as f64)),
+        entry("input_size", Json::Number(mlp.inputs().len() as f64)),
+        entry(
+            "normalization",
+            Json::String(normalization_name(mlp.normalization()).to_string()),
+        ),
+        entry("layers", Json::Array(layers)),
+    ];
+    let layer_tensors = 2 * mlp.sizes().len();
+    if tensors.len() > layer_tensors {
+        let norms = (layer_tensors..tensors.len())
+            .step_by(2)
+            .map(|index| {
+                Json::Object(vec![
+                    entry("weight", numbers(&values(index))),
+                    entry("bias", numbers(&values(index + 1))),
+                ])
+            })
+            .collect();
+        model.push(entry("norms", Json::Array(norms)));
+    }
+    let buffers = mlp.buffers();
+    if !buffers.is_empty() {
+        model.push(entry("buffers", numbers(&buffers)));
+    }
+    return Json::Object(model).pretty() + "\n";
+}
+
+fn field<'a>(object: &'a Json, key: &str) -> io::Result<&'a Json> {
+    return object
+        .get(key)
+        .ok_or_else(|| invalid(&format!("model JSON has no {}", key)));
+}
+
+pub(super) fn read_number(value: &Json) -> io::Result<f64> {
+    return match value {
+        Json::Number(number) => Ok(*number),
+        Json::String(s) if s == "NaN" => Ok(f64::NAN),
+        Json::String(s) if s == "Infinity" => Ok(f64::INFINITY),
+        Json::String(s) if s == "-Infinity" => Ok(f64::NEG_INFINITY),
+        _ => Err(invalid(&format!("{} is not a number", value))),
+    };
+}
+
+fn read_numbers(value: &Json) -> io::Result<Vec<f64>> {
+    return value
+        .as_array()
+        .ok_or_else(|| invalid(&format!("{} is not an array", value)))?
+        .iter()
+        .map(read_number)
+        .collect();
+}
+
+fn read_size(object: &Json, key: &str) -> io::Result<usize> {
+    return field(object, key)?
+        .as_usize()
+        .ok_or_else(|| invalid(&format!("{} is not a size", key)));
+}
+
+pub fn decode_json_model(text: &str) -> io::Result<MLP> {
+    let model = Json::parse(text)?;
+    if field(&model, "format")?.as_str() != Some(FORMAT) {
+        return Err(invalid("not an oxide-mlp model"));
+    }
+    let version = read_size(&model, "version")?;
+    if version != VERSION {
+        return Err(invalid(&format!("unsupported version {}", version)));
+    }
+    let input_size = read_size(&model, "input_size")?;
+    let normalization = match field(&model, "normalization")?.as_str() {
+        Some("none") => Normalization::None,
+        Some("running") => Normalization::Running,
+        Some("layer") => Normalization::Layer,
+        _ => return Err(invalid("unknown normalization")),
+    };
+
+    let layers = field(&model, "layers")?
+        .as_array()
+        .ok_or_else(|| invalid("layers is not an array"))?;
+    let mut sizes: Vec<usize> = vec![];
+    let mut activations: Vec<Activation> = vec![];
+    let mut loaded: Vec<(String, Vec<usize>, Vec<f64>)> = vec![];
+    let mut columns = input_size;
+    for (index, layer) in layers.iter().enumerate() {
+        let size = read_size(layer, "size")?;
+        let activation = match field(layer, "activation")?.as_str() {
+            Some("identity") => Activation::Identity,
+            Some("relu") => Activation::ReLU,
+            Some("leaky_relu") => Activation::LeakyReLU(read_number(field(layer, "slope")?)?),
+            Some("elu") => Activation::ELU,
+            Some("gelu") => Activation::GELU,
+            Some("silu") => Activation::SiLU,
+            Some("tanh") => Activation::Tanh,
+            Some("sigmoid") => Activation::Sigmoid,
+            Some("softplus") => Activation::Softplus,
+            Some("softmax") => Activation::Softmax,
+            _ => {
+                return Err(invalid(&format!(
+                    "layer {} has an unknown activation",
+                    index
+                )))
+            }
+        };
+        let rows = field(layer, "weight")?
+            .as_array()
+            .ok_or_else(|| invalid("weight is not an array"))?;
+        let bias = read_numbers(field(layer, "bias")?)?;
+        // checked before building, the sizes are untrusted
+        if rows.len() != size || bias.len() != size {
+            return Err(invalid(&format!(
+                "layer {} has {} rows and {} biases for size {}",
+                index,
+                rows.len(),
+                bias.len(),
+                size
+            )));
+        }
+        let mut weight: Vec<f64> = vec![];
+        for row in rows.iter() {
+            let row = read_numbers(row)?;
+            if row.len() != columns {
+                return Err(invalid(&format!(
+                    "layer {} has a row of {} weights",
+                    index,
+                    row.len()
+                )));
+            }
+            weight.extend(row);
+        }
+        loaded.push((
+            format!("layers.{}.weight", index),
+            vec![size, columns],
+            weight,
+        ));
+        loaded.push((format!("layers.{}.bias", index), vec![size], bias));
+        sizes.push(size);
+        activations.push(activation);
+        columns = size;
+    }
+    if sizes.is_empty() || sizes.contains(&0) || input_size == 0 {
+        return Err(invalid("empty layer"));
+    }
+
+    if let Some(norms) = model.get("norms") {
+        let norms = norms
+            .as_array()
+            .ok_or_else(|| invalid("norms is not an array"))?;
+        for (index, norm) in norms.iter().enumerate() {
+            for kind in ["weight", "bias"] {
+                let values = read_numbers(field(norm, kind)?)?;
+                loaded.push((
+                    format!("norms.{}.{}", index, kind),
+                    vec![values.len()],
+                    values,
+                ));
+            }
+        }
+    }
+
+    let buffers = match model.get("buffers") {
+        Some(buffers) => read_numbers(buffers)?,
+        None => vec![],
+    };
+    match mlp_counts(input_size, &sizes, normalization) {
+        Some((_, count)) if count == buffers.len() => {}
+        _ => return Err(invalid("buffers do not match the architecture")),
+    }
+
+    let mut mlp = MLP::with_normalization(sizes, activations, normalization, input_size);
+    // the flat buffers under the names `assign_mlp_tensors` expects
+    let mut offset = 0;
+    for (name, shape, current) in mlp_buffers(&mlp) {
+        loaded.push((
+            name,
+            shape,
+            buffers[offset..offset + current.len()].to_vec(),
+        ));
+        offset += current.len();
+    }
+    assign_mlp_tensors(&mut mlp, loaded)?;
+    return Ok(mlp);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::engine::randomize;
+
+    fn random_mlp() -> MLP {
+        let mut mlp = MLP::with_normalization(
+            vec![3, 2],
+            vec![Activation::LeakyReLU(0.1), Activation::Softmax],
+            Normalization::Running,
+            4,
+        );
+        randomize(&mlp.parameters(), 1.0, 17);
+        mlp.forward(&[0.1, 0.2, 0.3, 0.4]);
+        mlp.eval();
+        return mlp;
+    }
+
+    #[test]
+    fn test_round_trip_is_exact() {
+        let mut mlp = random_mlp();
+        let text = encode_json_model(&mlp);
+        assert!(text.contains("\"activation\": \"leaky_relu\",\n"));
+        let mut loaded = decode_json_model(&text).unwrap();
+        loaded.eval();
+        assert_eq!(encode_json_model(&loaded), text);
+
+        let inputs = [1.0, -2.0, 0.5, 3.0];
+        let expected: Vec<u64> = mlp.forward(&inputs).iter().map(|v| v.to_bits()).collect();
+        let actual: Vec<u64> = loaded
+            .forward(&inputs)
+            .iter()
+            .map(|v| v.to_bits())
+            .collect();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_hand_edited_model() {
+        let text = r#"{
+          "format": "oxide-mlp", "version": 1, "input_size": 2, "normalization": "none",
+          "layers": [
+            {"size": 1, "activation": "identity", "weight": [[0.5, "NaN"]], "bias": [0.25]}
+          ]
+        }"#;
+        let mlp = decode_json_model(text).unwrap();
+        let values: Vec<f64> = mlp.parameters().iter().map(|p| p.borrow().value).collect();
+        assert_eq!(values[..2], [0.25, 0.5]);
+        assert!(values[2].is_nan());
+        assert!(encode_json_model(&mlp).contains("[0.5,\"NaN\"]"));
+
+        let wrong = text.replace("[[0.5, \"NaN\"]]", "[[0.5]]");
+        assert!(decode_json_model(&wrong).is_err());
+
+        // sizes must agree with the stored rows and biases
+        let huge = text
+            .replace("\"size\": 1", "\"size\": 10000000000")
+            .replace("[[0.5, \"NaN\"]]", "[]");
+        assert!(decode_json_model(&huge).is_err());
+        let short_bias = text.replace("[0.25]", "[]");
+        assert!(decode_json_model(&short_bias).is_err());
+    }
+}
@@ -0,0 +1,52 @@
+mod binary;
+mod checkpoint;
+mod graph_file;
+mod json;
+mod json_model;
+mod npy;
+mod onnx;
+mod protobuf;
+mod safetensors;
+mod tensors;
+mod zip;
+
+pub use binary::decode_mlp;
+pub use binary::encode_mlp;
+pub use binary::load_mlp;
+pub use binary::save_mlp;
+pub use checkpoint::Checkpoint;
+pub use checkpoint::CheckpointManager;
+pub use graph_file::decode_graph;
+pub use graph_file::encode_graph;
+pub use graph_file::load_graph;
+pub use graph_file::save_graph;
+pub use json::Json;
+pub use json_model::decode_json_model;
+pub use json_model::encode_json_model;
+pub use json_model::load_json_model;
+pub use json_model::save_json_model;
+pub use npy::decode_npy;
+pub use npy::decode_npz;
+pub use npy::encode_npy;
+pub use npy::encode_npz;
+pub use npy::load_npy;
+pub use npy::load_npz;
+pub use npy::load_npz_weights;
+pub use npy::save_npy;
+pub use npy::save_npz;
+pub use npy::save_npz_weights;
+pub use npy::NpyArray;
+pub use npy::NpyDtype;
+pub use onnx::decode_onnx;
+pub use onnx::encode_onnx;
+pub use onnx::load_onnx;
+pub use onnx::save_onnx;
+pub use safetensors::decode_safetensors;
+pub use safetensors::encode_safetensors;
+pub use safetensors::load_safetensors;
+pub use safetensors::save_safetensors;
+pub use tensors::assign_mlp_tensors;
+pub use tensors::mlp_buffers;
+pub use tensors::mlp_tensor_values;
+pub use tensors::mlp_tensors;
+pub use tensors::NamedTensor;
@@ -0,0 +1,328 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::nn::MLP;
+use crate::serialize::assign_mlp_tensors;
+use crate::serialize::binary::invalid;
+use crate::serialize::mlp_tensor_values;
+use crate::serialize::zip::read_zip;
+use crate::serialize::zip::write_zip;
+
+static MAGIC: &[u8; 6] = b"\x93NUMPY";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NpyDtype {
+    F32,
+    F64,
+    U8,
+}
+
+impl NpyDtype {
+    fn descr(&self) -> &'static str {
+        return match self {
+            NpyDtype::F32 => "<f4",
+            NpyDtype::F64 => "<f8",
+            NpyDtype::U8 => "|u1",
+        };
+    }
+
+    fn width(&self) -> usize {
+        return match self {
+            NpyDtype::F32 => 4,
+            NpyDtype::F64 => 8,
+            NpyDtype::U8 => 1,
+        };
+    }
+}
+
+// A C-order array, the values are widened to f64 whatever the stored dtype.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NpyArray {
+    pub shape: Vec<usize>,
+    pub data: Vec<f64>,
+    pub dtype: NpyDtype,
+}
+
+impl NpyArray {
+    pub fn new(shape: Vec<usize>, data: Vec<f64>, dtype: NpyDtype) -> NpyArray {
+        assert_eq!(shape.iter().product::<usize>(), data.len());
+        return NpyArray { shape, data, dtype };
+    }
+
+    // Splits along the first axis, e.g. one row per sample for `MLP::set`.
+    pub fn rows(&self) -> Vec<Vec<f64>> {
+        assert!(!self.shape.is_empty());
+        let width: usize = self.shape[1..].iter().product();
+        if width == 0 {
+            return vec![vec![]; self.shape[0]];
+        }
+        return self.data.chunks(width).map(|row| row.to_vec()).collect();
+    }
+}
+
+pub fn save_npy(array: &NpyArray, path: &Path) -> io::Result<()> {
+    return fs::write(path, encode_npy(array));
+}
+
+pub fn load_npy(path: &Path) -> io::Result<NpyArray> {
+    return decode_npy(&fs::read(path)?);
+}
+
+pub fn encode_npy(array: &NpyArray) -> Vec<u8> {
+    let dimensions: Vec<String> = array.shape.iter().map(|d| d.to_string()).collect();
+    let shape = match dimensions.len() {
+        1 => format!("({},)", dimensions[0]),
+        _ => format!("({})", dimensions.join(", ")),
+    };
+    let mut header = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}",
+        array.dtype.descr(),
+        shape
+    );
+    // the data starts 64-byte aligned, the header ends with a newline
+    let prefix = match header.len() + 11 < 65536 {
+        true => 10,
+        false => 12,
+    };
+    while !(prefix + header.len() + 1).is_multiple_of(64) {
+        header.push(' ');
+    }
+    header.push('\n');
+
+    let mut bytes: Vec<u8> = MAGIC.to_vec();
+    match prefix {
+        10 => {
+            bytes.extend_from_slice(&[1, 0]);
+            bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+        }
+        _ => {
+            bytes.extend_from_slice(&[2, 0]);
+            bytes.extend_from_slice(&(header.len() as u32).to_le_bytes());
+        }
+    }
+    bytes.extend_from_slice(header.as_bytes());
+    for value in array.data.iter() {
+        match array.dtype {
+            NpyDtype::F64 => bytes.extend_from_slice(&value.to_le_bytes()),
+            NpyDtype::F32 => bytes.extend_from_slice(&(*value as f32).to_le_bytes()),
+            NpyDtype::U8 => {
+                assert!(
+                    *value >= 0.0 && *value <= 255.0 && value.fract() == 0.0,
+                    "{} is not a u8",
+                    value
+                );
+                bytes.push(*value as u8);
+            }
+        }
+    }
+    return bytes;
+}
+
+// Returns what follows `'key':` in a header dictionary.
+fn header_field<'a>(header: &'a str, key: &str) -> io::Result<&'a str> {
+    let pattern = format!("'{}':", key);
+    let start = header
+        .find(&pattern)
+        .ok_or_else(|| invalid(&format!("npy header has no {}", key)))?;
+    return Ok(header[start + pattern.len()..].trim_start());
+}
+
+pub fn decode_npy(bytes: &[u8]) -> io::Result<NpyArray> {
+    if bytes.len() < 10 || &bytes[..6] != MAGIC {
+        return Err(invalid("not an npy file"));
+    }
+    let (header_length, header_start) = match bytes[6] {
+        1 => (u16::from_le_bytes([bytes[8], bytes[9]]) as usize, 10),
+        2 | 3 if bytes.len() >= 12 => (
+            u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]) as usize,
+            12,
+        ),
+        version => return Err(invalid(&format!("unsupported npy version {}", version))),
+    };
+    let header = bytes
+        .get(header_start..header_start + header_length)
+        .and_then(|header| std::str::from_utf8(header).ok())
+        .ok_or_else(|| invalid("truncated npy header"))?;
+
+    let descr = header_field(header, "descr")?;
+    let quote = descr.chars().next().unwrap_or(' ');
+    let descr = descr[1..]
+        .split(quote)
+        .next()
+        .filter(|_| quote == '\'' || quote == '"')
+        .ok_or_else(|| invalid("npy header has an invalid descr"))?;
+    let (big_endian, dtype) = match descr {
+        "<f8" => (false, NpyDtype::F64),
+        ">f8" => (true, NpyDtype::F64),
+        "<f4" => (false, NpyDtype::F32),
+        ">f4" => (true, NpyDtype::F32),
+        "|u1" | "<u1" | ">u1" => (false, NpyDtype::U8),
+        _ => return Err(invalid(&format!("unsupported npy dtype {}", descr))),
+    };
+    if !header_field(header, "fortran_order")?.starts_with("False") {
+        return Err(invalid("only C order npy arrays are supported"));
+    }
+    let shape = header_field(header, "shape")?;
+    let shape: Vec<usize> = shape
+        .strip_prefix('(')
+        .and_then(|shape| shape.split(')').next())
+        .ok_or_else(|| invalid("npy header has an invalid shape"))?
+        .split(',')
+        .map(|d| d.trim())
+        .filter(|d| !d.is_empty())
+        .map(|d| {
+            d.parse::<usize>()
+                .map_err(|_| invalid("npy header has an invalid shape"))
+        })
+        .collect::<io::Result<Vec<usize>>>()?;
+
+    let count = shape
+        .iter()
+        .try_fold(1usize, |count, d| count.checked_mul(*d))
+        .ok_or_else(|| invalid("npy array is too large"))?;
+    let data = &bytes[header_start + header_length..];
+    if Some(data.len()) != count.checked_mul(dtype.width()) {
+        return Err(invalid(&format!("npy data holds {} bytes", data.len())));
+    }
+    let data: Vec<f64> = data
+        .chunks(dtype.width())
+        .map(|chunk| {
+            let mut chunk = chunk.to_vec();
+            if big_endian {
+                chunk.reverse();
+            }
+            return match dtype {
+                NpyDtype::F64 => f64::from_le_bytes(chunk.try_into().unwrap()),
+                NpyDtype::F32 => f32::from_le_bytes(chunk.try_into().unwrap()) as f64,
+                NpyDtype::U8 => chunk[0] as f64,
+            };
+        })
+        .collect();
+    return Ok(NpyArray { shape, data, dtype });
+}
+
+pub fn save_npz(arrays: &[(String, NpyArray)], path: &Path) -> io::Result<()> {
+    return fs::write(path, encode_npz(arrays));
+}
+
+pub fn load_npz(path: &Path) -> io::Result<Vec<(String, NpyArray)>> {
+    return decode_npz(&fs::read(path)?);
+}
+
+// Uncompressed archives as written by `numpy.savez`, one "{name}.npy" per array.
+pub fn encode_npz(arrays: &[(String, NpyArray)]) -> Vec<u8> {
+    let entries: Vec<(String, Vec<u8>)> = arrays
+        .iter()
+        .map(|(name, array)| (format!("{}.npy", name), encode_npy(array)))
+        .collect();
+    return write_zip(&entries);
+}
+
+pub fn decode_npz(bytes: &[u8]) -> io::Result<Vec<(String, NpyArray)>> {
+    let mut arrays: Vec<(String, NpyArray)> = vec![];
+    for (name, data) in read_zip(bytes)? {
+        let name = name.strip_suffix(".npy").unwrap_or(&name).to_string();
+        arrays.push((name, decode_npy(&data)?));
+    }
+    return Ok(arrays);
+}
+
+// Weights and running statistics are stored under the names of
+// `mlp_tensor_values`, e.g. "layers.0.weight" or "norms.0.running_mean".
+pub fn save_npz_weights(mlp: &MLP, path: &Path) -> io::Result<()> {
+    let arrays: Vec<(String, NpyArray)> = mlp_tensor_values(mlp)
+        .into_iter()
+        .map(|(name, shape, data)| (name, NpyArray::new(shape, data, NpyDtype::F64)))
+        .collect();
+    return save_npz(&arrays, path);
+}
+
+pub fn load_npz_weights(mlp: &mut MLP, path: &Path) -> io::Result<()> {
+    let loaded = load_npz(path)?
+        .into_iter()
+        .map(|(name, array)| (name, array.shape, array.data))
+        .collect();
+    return assign_mlp_tensors(mlp, loaded);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::nn::Activation;
+    use crate::nn::Module;
+    use crate::nn::Normalization;
+
+    #[test]
+    fn test_decode_numpy_output() {
+        // numpy.save of numpy.arange(6, dtype="<f4").reshape(2, 3)
+        let mut header = "{'descr': '<f4', 'fortran_order': False, 'shape': (2, 3), }".to_string();
+        while !(10 + header.len() + 1).is_multiple_of(64) {
+            header.push(' ');
+        }
+        header.push('\n');
+        let mut bytes: Vec<u8> = b"\x93NUMPY\x01\x00".to_vec();
+        bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(header.as_bytes());
+        for value in 0..6 {
+            bytes.extend_from_slice(&(value as f32).to_le_bytes());
+        }
+        assert_eq!(encode_npy(&decode_npy(&bytes).unwrap()), bytes);
+
+        let array = decode_npy(&bytes).unwrap();
+        assert_eq!(array.shape, vec![2, 3]);
+        assert_eq!(array.dtype, NpyDtype::F32);
+        assert_eq!(array.rows(), vec![vec![0.0, 1.0, 2.0], vec![3.0, 4.0, 5.0]]);
+
+        let mut fortran = bytes.clone();
+        let position = fortran.windows(5).position(|w| w == b"False").unwrap();
+        fortran[position..position + 5].copy_from_slice(b"True ");
+        assert!(decode_npy(&fortran).is_err());
+    }
+
+    #[test]
+    fn test_npz_dataset_and_weights() {
+        let pixels = NpyArray::new(
+            vec![2, 4],
+            vec![0.0, 255.0, 7.0, 1.0, 3.0, 2.0, 1.0, 0.0],
+            NpyDtype::U8,
+        );
+        let labels = NpyArray::new(vec![2], vec![1.0, 0.0], NpyDtype::F64);
+        let bytes = encode_npz(&[
+            ("x".to_string(), pixels.clone()),
+            ("y".to_string(), labels.clone()),
+        ]);
+        let arrays = decode_npz(&bytes).unwrap();
+        assert_eq!(
+            arrays,
+            vec![("x".to_string(), pixels), ("y".to_string(), labels)]
+        );
+
+        let normalized = || {
+            return MLP::with_normalization(
+                vec![3, 2],
+                vec![Activation::ReLU, Activation::Identity],
+                Normalization::Running,
+                4,
+            );
+        };
+        let mut mlp = normalized();
+        for row in arrays[0].1.rows() {
+            mlp.forward(&row);
+        }
+
+        let path = std::env::temp_dir().join("oxide_mlp_weights.npz");
+        save_npz_weights(&mlp, &path).unwrap();
+        let mut other = normalized();
+        for parameter in other.parameters().iter() {
+            parameter.borrow_mut().value = 0.5;
+        }
+        load_npz_weights(&mut other, &path).unwrap();
+        fs::remove_file(path).unwrap();
+        let values = |mlp: &MLP| -> Vec<f64> {
+            return mlp.parameters().iter().map(|p| p.borrow().value).collect();
+        };
+        assert_eq!(values(&other), values(&mlp));
+        assert_eq!(other.buffers(), mlp.buffers());
+    }
+}
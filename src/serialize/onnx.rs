@@ -0,0 +1,580 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::nn::Activation;
+use crate::nn::Module;
+use crate::nn::Normalization;
+use crate::nn::MLP;
+use crate::serialize::assign_mlp_tensors;
+use crate::serialize::binary::invalid;
+use crate::serialize::mlp_tensors;
+use crate::serialize::protobuf::decode_fields;
+use crate::serialize::protobuf::repeated_varints;
+use crate::serialize::protobuf::Field;
+use crate::serialize::protobuf::Message;
+
+// ONNX models, opset 13: every dense layer is a Gemm with DOUBLE initializers,
+// followed by a node for its activation.
+//
+// Convention of this crate, not of ONNX: a LeakyRelu `alpha` is a float attribute,
+// and when f32 cannot hold the slope exactly, the attribute's doc_string (field
+// 13 of AttributeProto) also carries the f64 slope as text, e.g. "0.01". Other
+// tools ignore the doc string and use the rounded float. On import the doc string
+// is only used when it rounds to the float, otherwise the float wins.
+
+// Field numbers and enum values of onnx.proto.
+const DOUBLE: u64 = 11;
+const FLOAT: u64 = 1;
+const ATTRIBUTE_FLOAT: u64 = 1;
+const ATTRIBUTE_INT: u64 = 2;
+const IR_VERSION: u64 = 8;
+const OPSET: u64 = 13;
+
+pub fn save_onnx(mlp: &MLP, path: &Path) -> io::Result<()> {
+    return fs::write(path, encode_onnx(mlp)?);
+}
+
+pub fn load_onnx(path: &Path) -> io::Result<MLP> {
+    return decode_onnx(&fs::read(path)?);
+}
+
+// A model survives a round trip bit for bit, see the convention for LeakyRelu
+// slopes above.
+pub fn encode_onnx(mlp: &MLP) -> io::Result<Vec<u8>> {
+    if mlp.normalization() != Normalization::None {
+        return Err(invalid("ONNX export of normalized MLPs is not supported"));
+    }
+    let mut graph = Message::new();
+    graph.string(2, "mlp");
+
+    let tensors = mlp_tensors(mlp);
+    let activations = mlp.activations();
+    let mut current = "input".to_string();
+    for (index, activation) in activations.iter().enumerate() {
+        let (weight, bias) = (&tensors[2 * index], &tensors[2 * index + 1]);
+        for tensor in [weight, bias] {
+            let values: Vec<f64> = tensor.nodes.iter().map(|n| n.borrow().value).collect();
+            graph.message(5, &tensor_proto(&tensor.name, &tensor.shape, &values));
+        }
+
+        let last = index + 1 == activations.len();
+        let linear = match (last, *activation) {
+            (true, Activation::Identity) => "output".to_string(),
+            _ => format!("layers.{}.linear", index),
+        };
+        let mut gemm = node(
+            "Gemm",
+            &format!("layers.{}.gemm", index),
+            &[&current, &weight.name, &bias.name],
+            &linear,
+        );
+        gemm.message(5, &int_attribute("transB", 1));
+        graph.message(1, &gemm);
+        current = linear;
+
+        let (op_type, attribute) = match activation {
+            Activation::Identity => continue,
+            Activation::ReLU => ("Relu", None),
+            Activation::LeakyReLU(slope) => ("LeakyRelu", Some(float_attribute("alpha", *slope))),
+            Activation::ELU => ("Elu", Some(float_attribute("alpha", 1.0))),
+            Activation::Tanh => ("Tanh", None),
+            Activation::Sigmoid => ("Sigmoid", None),
+            Activation::Softplus => ("Softplus", None),
+            Activation::Softmax => ("Softmax", Some(int_attribute("axis", -1))),
+            Activation::GELU | Activation::SiLU => {
+                return Err(invalid(&format!(
+                    "{:?} has no ONNX operator in opset {}",
+                    activation, OPSET
+                )))
+            }
+        };
+        let output = match last {
+            true => "output".to_string(),
+            false => format!("layers.{}.activation", index),
+        };
+        let mut activation_node = node(
+            op_type,
+            &format!("layers.{}.{}", index, op_type),
+            &[&current],
+            &output,
+        );
+        if let Some(attribute) = attribute {
+            activation_node.message(5, &attribute);
+        }
+        graph.message(1, &activation_node);
+        current = output;
+    }
+    graph.message(11, &value_info("input", mlp.inputs().len()));
+    graph.message(12, &value_info("output", mlp.outputs().len()));
+
+    let mut opset = Message::new();
+    opset.string(1, "").varint(2, OPSET);
+    let mut model = Message::new();
+    model
+        .varint(1, IR_VERSION)
+        .string(2, "oxide")
+        .message(8, &opset)
+        .message(7, &graph);
+    return Ok(model.into_bytes());
+}
+
+fn tensor_proto(name: &str, shape: &[usize], values: &[f64]) -> Message {
+    let mut tensor = Message::new();
+    for dimension in shape.iter() {
+        tensor.varint(1, *dimension as u64);
+    }
+    let raw: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+    tensor.varint(2, DOUBLE).string(8, name).bytes(9, &raw);
+    return tensor;
+}
+
+fn node(op_type: &str, name: &str, inputs: &[&str], output: &str) -> Message {
+    let mut node = Message::new();
+    for input in inputs.iter() {
+        node.string(1, input);
+    }
+    node.string(2, output).string(3, name).string(4, op_type);
+    return node;
+}
+
+fn int_attribute(name: &str, value: i64) -> Message {
+    let mut attribute = Message::new();
+    attribute
+        .string(1, name)
+        .int64(3, value)
+        .varint(20, ATTRIBUTE_INT);
+    return attribute;
+}
+
+fn float_attribute(name: &str, value: f64) -> Message {
+    let mut attribute = Message::new();
+    attribute
+        .string(1, name)
+        .float(2, value as f32)
+        .varint(20, ATTRIBUTE_FLOAT);
+    if value as f32 as f64 != value {
+        attribute.string(13, &format!("{:?}", value));
+    }
+    return attribute;
+}
+
+// A DOUBLE tensor of shape [N, size] with a symbolic batch dimension.
+fn value_info(name: &str, size: usize) -> Message {
+    let mut batch = Message::new();
+    batch.string(2, "N");
+    let mut features = Message::new();
+    features.varint(1, size as u64);
+    let mut shape = Message::new();
+    shape.message(1, &batch).message(1, &features);
+    let mut tensor_type = Message::new();
+    tensor_type.varint(1, DOUBLE).message(2, &shape);
+    let mut type_proto = Message::new();
+    type_proto.message(1, &tensor_type);
+    let mut info = Message::new();
+    info.string(1, name).message(2, &type_proto);
+    return info;
+}
+
+struct Tensor {
+    name: String,
+    shape: Vec<usize>,
+    values: Vec<f64>,
+}
+
+// (name, float value, int value, doc string)
+type Attribute = (String, Option<f32>, Option<i64>, Option<String>);
+
+struct Node {
+    op_type: String,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+    attributes: Vec<Attribute>,
+}
+
+impl Node {
+    fn float(&self, name: &str, default: f32) -> f32 {
+        return self
+            .attributes
+            .iter()
+            .find(|(n, _, _, _)| n == name)
+            .and_then(|(_, f, _, _)| *f)
+            .unwrap_or(default);
+    }
+
+    // The f64 slope of the doc string convention described at the top of the file.
+    fn exact_float(&self, name: &str, default: f64) -> f64 {
+        let attribute = self.attributes.iter().find(|(n, _, _, _)| n == name);
+        return match attribute {
+            Some((_, Some(float), _, doc)) => {
+                match doc.as_ref().and_then(|doc| doc.parse::<f64>().ok()) {
+                    Some(exact) if exact as f32 == *float => exact,
+                    _ => *float as f64,
+                }
+            }
+            _ => default,
+        };
+    }
+
+    fn int(&self, name: &str, default: i64) -> i64 {
+        return self
+            .attributes
+            .iter()
+            .find(|(n, _, _, _)| n == name)
+            .and_then(|(_, _, i, _)| *i)
+            .unwrap_or(default);
+    }
+}
+
+fn parse_tensor(bytes: &[u8]) -> io::Result<Tensor> {
+    let mut name = String::new();
+    let mut shape: Vec<usize> = vec![];
+    let mut data_type = 0;
+    let mut values: Vec<f64> = vec![];
+    let mut raw: Option<&[u8]> = None;
+    for (number, field) in decode_fields(bytes)? {
+        match number {
+            1 => shape.extend(repeated_varints(&field)?.iter().map(|d| *d as usize)),
+            2 => data_type = field.as_u64()?,
+            4 => match field {
+                Field::Fixed32(bits) => values.push(f32::from_bits(bits) as f64),
+                _ => values.extend(
+                    field
+                        .as_bytes()?
+                        .chunks_exact(4)
+                        .map(|c| f32::from_le_bytes(c.try_into().unwrap()) as f64),
+                ),
+            },
+            8 => name = field.as_string()?,
+            9 => raw = Some(field.as_bytes()?),
+            10 => match field {
+                Field::Fixed64(bits) => values.push(f64::from_bits(bits)),
+                _ => values.extend(
+                    field
+                        .as_bytes()?
+                        .chunks_exact(8)
+                        .map(|c| f64::from_le_bytes(c.try_into().unwrap())),
+                ),
+            },
+            _ => {}
+        }
+    }
+    if let Some(raw) = raw {
+        values = match data_type {
+            FLOAT => raw
+                .chunks_exact(4)
+                .map(|c| f32::from_le_bytes(c.try_into().unwrap()) as f64)
+                .collect(),
+            DOUBLE => raw
+                .chunks_exact(8)
+                .map(|c| f64::from_le_bytes(c.try_into().unwrap()))
+                .collect(),
+            _ => vec![],
+        };
+    }
+    if data_type != FLOAT && data_type != DOUBLE {
+        return Err(invalid(&format!(
+            "{} has unsupported data type {}",
+            name, data_type
+        )));
+    }
+    if Some(values.len()) != shape.iter().try_fold(1usize, |c, d| c.checked_mul(*d)) {
+        return Err(invalid(&format!(
+            "{} does not match its shape {:?}",
+            name, shape
+        )));
+    }
+    return Ok(Tensor {
+        name,
+        shape,
+        values,
+    });
+}
+
+fn parse_node(bytes: &[u8]) -> io::Result<Node> {
+    let mut node = Node {
+        op_type: String::new(),
+        inputs: vec![],
+        outputs: vec![],
+        attributes: vec![],
+    };
+    for (number, field) in decode_fields(bytes)? {
+        match number {
+            1 => node.inputs.push(field.as_string()?),
+            2 => node.outputs.push(field.as_string()?),
+            4 => node.op_type = field.as_string()?,
+            5 => {
+                let mut attribute = (String::new(), None, None, None);
+                for (number, field) in decode_fields(field.as_bytes()?)? {
+                    match number {
+                        1 => attribute.0 = field.as_string()?,
+                        2 => attribute.1 = Some(field.as_f32()?),
+                        3 => attribute.2 = Some(field.as_u64()? as i64),
+                        13 => attribute.3 = Some(field.as_string()?),
+                        _ => {}
+                    }
+                }
+                node.attributes.push(attribute);
+            }
+            _ => {}
+        }
+    }
+    return Ok(node);
+}
+
+// A dense layer collected from the graph, weights as [outputs, inputs].
+struct DenseLayer {
+    inputs: usize,
+    outputs: usize,
+    weight: Vec<f64>,
+    bias: Vec<f64>,
+    has_bias: bool,
+    activation: Option<Activation>,
+}
+
+fn transpose(values: &[f64], rows: usize, columns: usize) -> Vec<f64> {
+    let mut transposed = vec![0.0; values.len()];
+    for row in 0..rows {
+        for column in 0..columns {
+            transposed[column * rows + row] = values[row * columns + column];
+        }
+    }
+    return transposed;
+}
+
+// Reads a chain of Gemm, or MatMul with an optional Add, each followed by at most
+// one activation (Relu, LeakyRelu, Elu, Sigmoid, Tanh, Softplus, Softmax).
+pub fn decode_onnx(bytes: &[u8]) -> io::Result<MLP> {
+    let graph = decode_fields(bytes)?
+        .into_iter()
+        .find(|(number, _)| *number == 7)
+        .ok_or_else(|| invalid("ONNX model has no graph"))?
+        .1;
+    let mut nodes: Vec<Node> = vec![];
+    let mut initializers: Vec<Tensor> = vec![];
+    let mut inputs: Vec<String> = vec![];
+    let mut outputs: Vec<String> = vec![];
+    for (number, field) in decode_fields(graph.as_bytes()?)? {
+        let name = |bytes: &[u8]| -> io::Result<String> {
+            let fields = decode_fields(bytes)?;
+            return match fields.iter().find(|(number, _)| *number == 1) {
+                Some((_, name)) => name.as_string(),
+                None => Err(invalid("ONNX value has no name")),
+            };
+        };
+        match number {
+            1 => nodes.push(parse_node(field.as_bytes()?)?),
+            5 => initializers.push(parse_tensor(field.as_bytes()?)?),
+            11 => inputs.push(name(field.as_bytes()?)?),
+            12 => outputs.push(name(field.as_bytes()?)?),
+            _ => {}
+        }
+    }
+    let initializer = |name: &str| -> io::Result<&Tensor> {
+        return initializers
+            .iter()
+            .find(|tensor| tensor.name == name)
+            .ok_or_else(|| invalid(&format!("{} is not an initializer", name)));
+    };
+
+    // older exporters list the initializers among the inputs
+    let mut current = inputs
+        .iter()
+        .find(|input| initializers.iter().all(|tensor| tensor.name != **input))
+        .ok_or_else(|| invalid("ONNX graph has no input"))?
+        .clone();
+    let mut layers: Vec<DenseLayer> = vec![];
+    for node in nodes.iter() {
+        let unsupported = || invalid(&format!("unsupported {} node", node.op_type));
+        let (first, second) = match node.inputs.as_slice() {
+            [first] => (first, None),
+            [first, second] | [first, second, _] => (first, Some(second)),
+            _ => return Err(unsupported()),
+        };
+        let open = layers.last_mut().filter(|layer| layer.activation.is_none());
+        match node.op_type.as_str() {
+            "Gemm" | "MatMul" => {
+                if *first != current {
+                    return Err(unsupported());
+                }
+                let weight = initializer(second.ok_or_else(unsupported)?)?;
+                let transposed = node.op_type == "Gemm" && node.int("transB", 0) == 1;
+                if weight.shape.len() != 2 || node.int("transA", 0) != 0 {
+                    return Err(unsupported());
+                }
+                let alpha = node.float("alpha", 1.0) as f64;
+                let (rows, columns) = (weight.shape[0], weight.shape[1]);
+                let (inputs, outputs, weight) = match transposed {
+                    true => (columns, rows, weight.values.clone()),
+                    false => (rows, columns, transpose(&weight.values, rows, columns)),
+                };
+                let mut layer = DenseLayer {
+                    inputs,
+                    outputs,
+                    weight: weight.iter().map(|w| w * alpha).collect(),
+                    bias: vec![0.0; outputs],
+                    has_bias: false,
+                    activation: None,
+                };
+                if node.op_type == "Gemm" && node.inputs.len() == 3 {
+                    let beta = node.float("beta", 1.0) as f64;
+                    let bias = initializer(&node.inputs[2])?;
+                    if bias.values.len() != outputs {
+                        return Err(invalid("Gemm bias does not match its weights"));
+                    }
+                    layer.bias = bias.values.iter().map(|b| b * beta).collect();
+                    layer.has_bias = true;
+                }
+                layers.push(layer);
+            }
+            "Add" => {
+                let other = match (*first == current, second) {
+                    (true, Some(second)) => second,
+                    (false, Some(second)) if *second == current => first,
+                    _ => return Err(unsupported()),
+                };
+                let bias = initializer(other)?;
+                let layer = open
+                    .filter(|layer| !layer.has_bias && bias.values.len() == layer.outputs)
+                    .ok_or_else(|| invalid("Add must add a bias vector to a MatMul"))?;
+                layer.bias = bias.values.clone();
+                layer.has_bias = true;
+            }
+            "Identity" => {}
+            op_type => {
+                if *first != current {
+                    return Err(unsupported());
+                }
+                let activation = match op_type {
+                    "Relu" => Activation::ReLU,
+                    "LeakyRelu" => Activation::LeakyReLU(node.exact_float("alpha", 0.01)),
+                    "Elu" if node.float("alpha", 1.0) == 1.0 => Activation::ELU,
+                    "Sigmoid" => Activation::Sigmoid,
+                    "Tanh" => Activation::Tanh,
+                    "Softplus" => Activation::Softplus,
+                    "Softmax" if [-1, 1].contains(&node.int("axis", -1)) => Activation::Softmax,
+                    _ => return Err(unsupported()),
+                };
+                let layer = open.ok_or_else(|| invalid("activation without a dense layer"))?;
+                layer.activation = Some(activation);
+            }
+        }
+        current = node.outputs.first().ok_or_else(unsupported)?.clone();
+    }
+    if outputs.first() != Some(&current) {
+        return Err(invalid("ONNX graph output is not the end of the chain"));
+    }
+    if layers.is_empty() || layers.windows(2).any(|w| w[0].outputs != w[1].inputs) {
+        return Err(invalid("ONNX layer sizes do not chain"));
+    }
+
+    let sizes = layers.iter().map(|layer| layer.outputs).collect();
+    let activations = layers
+        .iter()
+        .map(|layer| layer.activation.unwrap_or(Activation::Identity))
+        .collect();
+    let mut mlp = MLP::with_activations(sizes, activations, layers[0].inputs);
+    let mut loaded: Vec<(String, Vec<usize>, Vec<f64>)> = vec![];
+    for (index, layer) in layers.into_iter().enumerate() {
+        let shape = vec![layer.outputs, layer.inputs];
+        loaded.push((format!("layers.{}.weight", index), shape, layer.weight));
+        loaded.push((
+            format!("layers.{}.bias", index),
+            vec![layer.outputs],
+            layer.bias,
+        ));
+    }
+    assign_mlp_tensors(&mut mlp, loaded)?;
+    return Ok(mlp);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::engine::randomize;
+
+    #[test]
+    fn test_round_trip() {
+        let mut mlp = MLP::with_activations(
+            vec![5, 4, 4, 3],
+            vec![
+                Activation::ReLU,
+                Activation::LeakyReLU(0.01),
+                Activation::Tanh,
+                Activation::Softmax,
+            ],
+            6,
+        );
+        randomize(&mlp.parameters(), 1.0, 13);
+        let path = std::env::temp_dir().join("oxide_mlp.onnx");
+        save_onnx(&mlp, &path).unwrap();
+        let mut loaded = load_onnx(&path).unwrap();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(loaded.sizes(), mlp.sizes());
+        assert_eq!(loaded.activations(), mlp.activations());
+        let inputs = [0.5, -0.25, 1.0, 2.0, -3.0, 0.125];
+        let expected: Vec<u64> = mlp.forward(&inputs).iter().map(|v| v.to_bits()).collect();
+        let actual: Vec<u64> = loaded
+            .forward(&inputs)
+            .iter()
+            .map(|v| v.to_bits())
+            .collect();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_import_matmul_add_sigmoid() {
+        // float weights of shape [inputs, outputs] as written by most exporters
+        let mut weight = Message::new();
+        weight
+            .varint(1, 2)
+            .varint(1, 1)
+            .varint(2, FLOAT)
+            .string(8, "w");
+        let packed: Vec<u8> = [2.0f32, -1.0]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        weight.bytes(4, &packed);
+        let mut bias = Message::new();
+        bias.varint(1, 1)
+            .varint(2, FLOAT)
+            .float(4, 0.5)
+            .string(8, "b");
+
+        let mut graph = Message::new();
+        graph
+            .message(1, &node("MatMul", "matmul", &["x", "w"], "h"))
+            .message(1, &node("Add", "add", &["b", "h"], "z"))
+            .message(1, &node("Sigmoid", "sigmoid", &["z"], "y"))
+            .message(5, &weight)
+            .message(5, &bias)
+            .message(11, &value_info("x", 2))
+            .message(12, &value_info("y", 1));
+        let mut model = Message::new();
+        model.varint(1, IR_VERSION).message(7, &graph);
+
+        let mut mlp = decode_onnx(&model.into_bytes()).unwrap();
+        assert_eq!(mlp.activations(), vec![Activation::Sigmoid]);
+        let output = mlp.forward(&[1.0, 1.5]);
+        assert!((output[0] - 1.0 / (1.0 + (-1.0f64).exp())).abs() < 1e-12);
+
+        let mut kernel = Message::new();
+        kernel
+            .varint(1, 1)
+            .varint(1, 2)
+            .varint(2, DOUBLE)
+            .string(8, "w");
+        kernel.bytes(9, &[0u8; 16]);
+        let mut unsupported = Message::new();
+        unsupported
+            .message(1, &node("Conv", "conv", &["x", "w"], "y"))
+            .message(5, &kernel)
+            .message(11, &value_info("x", 2))
+            .message(12, &value_info("y", 1));
+        let mut model = Message::new();
+        model.message(7, &unsupported);
+        assert!(decode_onnx(&model.into_bytes()).is_err());
+    }
+}
@@ -0,0 +1,208 @@
+use std::io;
+
+use crate::serialize::binary::invalid;
+
+// Protocol buffer wire format, enough to write and read ONNX models.
+pub struct Message {
+    bytes: Vec<u8>,
+}
+
+fn push_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+impl Message {
+    pub fn new() -> Message {
+        return Message { bytes: vec![] };
+    }
+
+    fn key(&mut self, field: u32, wire_type: u8) {
+        push_varint(&mut self.bytes, ((field as u64) << 3) | wire_type as u64);
+    }
+
+    pub fn varint(&mut self, field: u32, value: u64) -> &mut Message {
+        self.key(field, 0);
+        push_varint(&mut self.bytes, value);
+        return self;
+    }
+
+    // Negative values take ten bytes, as for protobuf int64.
+    pub fn int64(&mut self, field: u32, value: i64) -> &mut Message {
+        return self.varint(field, value as u64);
+    }
+
+    pub fn float(&mut self, field: u32, value: f32) -> &mut Message {
+        self.key(field, 5);
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+        return self;
+    }
+
+    pub fn bytes(&mut self, field: u32, value: &[u8]) -> &mut Message {
+        self.key(field, 2);
+        push_varint(&mut self.bytes, value.len() as u64);
+        self.bytes.extend_from_slice(value);
+        return self;
+    }
+
+    pub fn string(&mut self, field: u32, value: &str) -> &mut Message {
+        return self.bytes(field, value.as_bytes());
+    }
+
+    pub fn message(&mut self, field: u32, value: &Message) -> &mut Message {
+        return self.bytes(field, &value.bytes);
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        return self.bytes;
+    }
+}
+
+impl Default for Message {
+    fn default() -> Message {
+        return Message::new();
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Field<'a> {
+    Varint(u64),
+    Fixed64(u64),
+    Bytes(&'a [u8]),
+    Fixed32(u32),
+}
+
+impl<'a> Field<'a> {
+    pub fn as_u64(&self) -> io::Result<u64> {
+        return match self {
+            Field::Varint(value) => Ok(*value),
+            _ => Err(invalid("protobuf: expected a varint")),
+        };
+    }
+
+    pub fn as_bytes(&self) -> io::Result<&'a [u8]> {
+        return match self {
+            Field::Bytes(bytes) => Ok(bytes),
+            _ => Err(invalid("protobuf: expected a length delimited field")),
+        };
+    }
+
+    pub fn as_string(&self) -> io::Result<String> {
+        return String::from_utf8(self.as_bytes()?.to_vec())
+            .map_err(|_| invalid("protobuf: string is not UTF-8"));
+    }
+
+    pub fn as_f32(&self) -> io::Result<f32> {
+        return match self {
+            Field::Fixed32(bits) => Ok(f32::from_bits(*bits)),
+            _ => Err(invalid("protobuf: expected a fixed32")),
+        };
+    }
+}
+
+fn read_varint(bytes: &[u8], position: &mut usize) -> io::Result<u64> {
+    let mut value: u64 = 0;
+    for shift in (0..64).step_by(7) {
+        let byte = *bytes
+            .get(*position)
+            .ok_or_else(|| invalid("protobuf: truncated varint"))?;
+        *position += 1;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    return Err(invalid("protobuf: varint too long"));
+}
+
+fn take<'a>(bytes: &'a [u8], position: &mut usize, count: usize) -> io::Result<&'a [u8]> {
+    let end = position
+        .checked_add(count)
+        .filter(|end| *end <= bytes.len())
+        .ok_or_else(|| invalid("protobuf: truncated field"))?;
+    let slice = &bytes[*position..end];
+    *position = end;
+    return Ok(slice);
+}
+
+// Splits a message into its (field number, value) pairs in wire order.
+pub fn decode_fields(bytes: &[u8]) -> io::Result<Vec<(u32, Field<'_>)>> {
+    let mut fields: Vec<(u32, Field<'_>)> = vec![];
+    let mut position = 0;
+    while position < bytes.len() {
+        let key = read_varint(bytes, &mut position)?;
+        let number = (key >> 3) as u32;
+        let field = match key & 7 {
+            0 => Field::Varint(read_varint(bytes, &mut position)?),
+            1 => {
+                let raw = take(bytes, &mut position, 8)?;
+                Field::Fixed64(u64::from_le_bytes(raw.try_into().unwrap()))
+            }
+            2 => {
+                let length = read_varint(bytes, &mut position)? as usize;
+                Field::Bytes(take(bytes, &mut position, length)?)
+            }
+            5 => {
+                let raw = take(bytes, &mut position, 4)?;
+                Field::Fixed32(u32::from_le_bytes(raw.try_into().unwrap()))
+            }
+            wire_type => {
+                return Err(invalid(&format!(
+                    "protobuf: unsupported wire type {}",
+                    wire_type
+                )))
+            }
+        };
+        fields.push((number, field));
+    }
+    return Ok(fields);
+}
+
+// Repeated integers may be written one per field or packed into a single one.
+pub fn repeated_varints(field: &Field) -> io::Result<Vec<u64>> {
+    return match field {
+        Field::Varint(value) => Ok(vec![*value]),
+        Field::Bytes(bytes) => {
+            let mut values: Vec<u64> = vec![];
+            let mut position = 0;
+            while position < bytes.len() {
+                values.push(read_varint(bytes, &mut position)?);
+            }
+            Ok(values)
+        }
+        _ => Err(invalid("protobuf: expected repeated varints")),
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut inner = Message::new();
+        inner.string(1, "relu");
+        let mut message = Message::new();
+        message
+            .varint(1, 300)
+            .int64(2, -1)
+            .float(3, 1.5)
+            .message(4, &inner)
+            .bytes(5, &[0x96, 0x01, 0x05]);
+        let bytes = message.into_bytes();
+        // field 1 varint 300 is the example of the protobuf documentation
+        assert_eq!(&bytes[..3], &[0x08, 0xac, 0x02]);
+
+        let fields = decode_fields(&bytes).unwrap();
+        assert_eq!(fields[0], (1, Field::Varint(300)));
+        assert_eq!(fields[1].1.as_u64().unwrap() as i64, -1);
+        assert_eq!(fields[2].1.as_f32().unwrap(), 1.5);
+        let inner = decode_fields(fields[3].1.as_bytes().unwrap()).unwrap();
+        assert_eq!(inner[0].1.as_string().unwrap(), "relu");
+        assert_eq!(repeated_varints(&fields[4].1).unwrap(), vec![150, 5]);
+        assert!(decode_fields(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
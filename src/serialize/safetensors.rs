@@ -0,0 +1,213 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::nn::MLP;
+use crate::serialize::assign_mlp_tensors;
+use crate::serialize::binary::invalid;
+use crate::serialize::mlp_tensor_values;
+use crate::serialize::Json;
+
+// safetensors layout: header length u64 little-endian, a JSON header mapping
+// tensor names to dtype, shape and byte offsets into the data that follows, then
+// the raw little-endian tensor data. Tensors are written as F64; F32 tensors are
+// accepted on load and widened.
+pub fn save_safetensors(mlp: &MLP, path: &Path) -> io::Result<()> {
+    return fs::write(path, encode_safetensors(mlp));
+}
+
+// Loads parameters and running statistics into `mlp`, which must have the
+// architecture they were saved from. Nothing is written unless every tensor matches.
+pub fn load_safetensors(mlp: &mut MLP, path: &Path) -> io::Result<()> {
+    return decode_safetensors(mlp, &fs::read(path)?);
+}
+
+pub fn encode_safetensors(mlp: &MLP) -> Vec<u8> {
+    let mut entries: Vec<(String, Json)> = vec![(
+        "__metadata__".to_string(),
+        Json::Object(vec![(
+            "format".to_string(),
+            Json::String("oxide".to_string()),
+        )]),
+    )];
+    let mut data: Vec<u8> = vec![];
+    for (name, shape, values) in mlp_tensor_values(mlp) {
+        let begin = data.len();
+        for value in values.iter() {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        let shape = shape.iter().map(|d| Json::Number(*d as f64)).collect();
+        entries.push((
+            name,
+            Json::Object(vec![
+                ("dtype".to_string(), Json::String("F64".to_string())),
+                ("shape".to_string(), Json::Array(shape)),
+                (
+                    "data_offsets".to_string(),
+                    Json::Array(vec![
+                        Json::Number(begin as f64),
+                        Json::Number(data.len() as f64),
+                    ]),
+                ),
+            ]),
+        ));
+    }
+
+    let mut header = Json::Object(entries).to_string().into_bytes();
+    // pad with spaces so the data starts 8-byte aligned
+    while !header.len().is_multiple_of(8) {
+        header.push(b' ');
+    }
+    let mut bytes: Vec<u8> = (header.len() as u64).to_le_bytes().to_vec();
+    bytes.extend(header);
+    bytes.extend(data);
+    return bytes;
+}
+
+pub fn decode_safetensors(mlp: &mut MLP, bytes: &[u8]) -> io::Result<()> {
+    if bytes.len() < 8 {
+        return Err(invalid("missing safetensors header"));
+    }
+    let header_length = u64::from_le_bytes(bytes[..8].try_into().unwrap());
+    if header_length > (bytes.len() - 8) as u64 {
+        return Err(invalid("header longer than the file"));
+    }
+    let header_end = 8 + header_length as usize;
+    let header =
+        std::str::from_utf8(&bytes[8..header_end]).map_err(|_| invalid("header is not UTF-8"))?;
+    let header = Json::parse(header)?;
+    let entries = header
+        .as_object()
+        .ok_or_else(|| invalid("header is not an object"))?;
+    let data = &bytes[header_end..];
+
+    let mut loaded: Vec<(String, Vec<usize>, Vec<f64>)> = vec![];
+    for (name, entry) in entries.iter() {
+        if name == "__metadata__" {
+            continue;
+        }
+        let (shape, values) = read_tensor(name, entry, data)?;
+        loaded.push((name.clone(), shape, values));
+    }
+    return assign_mlp_tensors(mlp, loaded);
+}
+
+fn read_tensor(name: &str, entry: &Json, data: &[u8]) -> io::Result<(Vec<usize>, Vec<f64>)> {
+    let field = |key: &str| {
+        return entry
+            .get(key)
+            .ok_or_else(|| invalid(&format!("{} has no {}", name, key)));
+    };
+    let shape: Vec<usize> = field("shape")?
+        .as_array()
+        .and_then(|dims| dims.iter().map(|d| d.as_usize()).collect())
+        .ok_or_else(|| invalid(&format!("{} has an invalid shape", name)))?;
+    let offsets: Option<Vec<usize>> = field("data_offsets")?
+        .as_array()
+        .and_then(|offsets| offsets.iter().map(|o| o.as_usize()).collect());
+    let (begin, end) = match offsets.as_deref() {
+        Some([begin, end]) if begin <= end && *end <= data.len() => (*begin, *end),
+        _ => return Err(invalid(&format!("{} has invalid data offsets", name))),
+    };
+    let bytes = &data[begin..end];
+    let count = shape
+        .iter()
+        .try_fold(1usize, |count, d| count.checked_mul(*d))
+        .ok_or_else(|| invalid(&format!("{} is too large", name)))?;
+
+    let dtype = field("dtype")?.as_str().unwrap_or("");
+    let width = match dtype {
+        "F64" => 8,
+        "F32" => 4,
+        _ => {
+            return Err(invalid(&format!(
+                "{} has unsupported dtype {}",
+                name, dtype
+            )))
+        }
+    };
+    if bytes.len() != count * width {
+        return Err(invalid(&format!("{} has {} data bytes", name, bytes.len())));
+    }
+    let values = bytes
+        .chunks(width)
+        .map(|chunk| match width {
+            8 => f64::from_le_bytes(chunk.try_into().unwrap()),
+            _ => f32::from_le_bytes(chunk.try_into().unwrap()) as f64,
+        })
+        .collect();
+    return Ok((shape, values));
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::engine::randomize;
+    use crate::nn::Activation;
+    use crate::nn::Module;
+    use crate::nn::Normalization;
+
+    fn mlp(seed: u64) -> MLP {
+        let mlp =
+            MLP::with_activations(vec![3, 2], vec![Activation::ReLU, Activation::Identity], 4);
+        randomize(&mlp.parameters(), 1.0, seed);
+        return mlp;
+    }
+
+    fn values(mlp: &MLP) -> Vec<f64> {
+        return mlp.parameters().iter().map(|p| p.borrow().value).collect();
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let source = mlp(1);
+        let mut target = mlp(2);
+        let path = std::env::temp_dir().join("oxide_mlp.safetensors");
+        save_safetensors(&source, &path).unwrap();
+        load_safetensors(&mut target, &path).unwrap();
+        fs::remove_file(path).unwrap();
+        assert_eq!(values(&target), values(&source));
+
+        let bytes = encode_safetensors(&source);
+        let header_length = u64::from_le_bytes(bytes[..8].try_into().unwrap()) as usize;
+        assert_eq!(header_length % 8, 0);
+        let header = Json::parse(std::str::from_utf8(&bytes[8..8 + header_length]).unwrap());
+        let header = header.unwrap();
+        let bias = header.get("layers.1.bias").unwrap();
+        assert_eq!(bias.get("data_offsets").unwrap().to_string(), "[168,184]");
+    }
+
+    #[test]
+    fn test_rejects_shape_mismatch() {
+        let bytes = encode_safetensors(&mlp(1));
+        let mut wider =
+            MLP::with_activations(vec![5, 2], vec![Activation::ReLU, Activation::Identity], 4);
+        let before = values(&wider);
+        let error = decode_safetensors(&mut wider, &bytes).unwrap_err();
+        assert!(error.to_string().contains("layers.0.weight"));
+        assert_eq!(values(&wider), before);
+    }
+
+    #[test]
+    fn test_running_statistics_round_trip() {
+        let normalized = || {
+            return MLP::with_normalization(
+                vec![3, 2],
+                vec![Activation::ReLU, Activation::Identity],
+                Normalization::Running,
+                4,
+            );
+        };
+        let mut source = normalized();
+        for sample in [[1.0, 2.0, 3.0, 4.0], [0.0, -2.0, 8.0, 1.0]] {
+            source.forward(&sample);
+        }
+        source.eval();
+        let mut target = normalized();
+        target.eval();
+        decode_safetensors(&mut target, &encode_safetensors(&source)).unwrap();
+        assert_eq!(target.buffers(), source.buffers());
+        let inputs = [0.5, 1.5, -1.0, 2.0];
+        assert_eq!(target.forward(&inputs), source.forward(&inputs));
+    }
+}
@@ -0,0 +1,241 @@
+use std::io;
+
+use crate::engine::ValueRef;
+use crate::nn::Module;
+use crate::nn::Normalization;
+use crate::nn::MLP;
+use crate::serialize::binary::invalid;
+
+// A parameter tensor of an `MLP`, its nodes in row-major order.
+pub struct NamedTensor {
+    pub name: String,
+    pub shape: Vec<usize>,
+    pub nodes: Vec<ValueRef>,
+}
+
+// Groups the parameters of an `MLP` into one `[outputs, inputs]` weight matrix and
+// one bias vector per layer ("layers.{i}.weight", "layers.{i}.bias"), followed by
+// the gains and biases of the normalizations ("norms.{i}.weight", "norms.{i}.bias").
+pub fn mlp_tensors(mlp: &MLP) -> Vec<NamedTensor> {
+    let parameters = mlp.parameters();
+    let mut tensors: Vec<NamedTensor> = vec![];
+    let mut offset = 0;
+    let mut input_sizes: Vec<usize> = vec![];
+    let mut input_size = mlp.inputs().len();
+    for (index, size) in mlp.sizes().iter().enumerate() {
+        let mut weights: Vec<ValueRef> = Vec::with_capacity(size * input_size);
+        let mut biases: Vec<ValueRef> = Vec::with_capacity(*size);
+        // every neuron holds its bias first, then its weights
+        for _ in 0..*size {
+            biases.push(parameters[offset].clone());
+            weights.extend_from_slice(&parameters[offset + 1..offset + 1 + input_size]);
+            offset += input_size + 1;
+        }
+        tensors.push(NamedTensor {
+            name: format!("layers.{}.weight", index),
+            shape: vec![*size, input_size],
+            nodes: weights,
+        });
+        tensors.push(NamedTensor {
+            name: format!("layers.{}.bias", index),
+            shape: vec![*size],
+            nodes: biases,
+        });
+        input_sizes.push(input_size);
+        input_size = *size;
+    }
+
+    if mlp.normalization() != Normalization::None {
+        for (index, size) in input_sizes.iter().enumerate() {
+            for kind in ["weight", "bias"] {
+                tensors.push(NamedTensor {
+                    name: format!("norms.{}.{}", index, kind),
+                    shape: vec![*size],
+                    nodes: parameters[offset..offset + size].to_vec(),
+                });
+                offset += size;
+            }
+        }
+    }
+    assert_eq!(offset, parameters.len());
+    return tensors;
+}
+
+// Running statistics of the normalizations, in `Module::buffers` order:
+// "norms.{i}.running_mean", "norms.{i}.running_var" and the sample count
+// "norms.{i}.count". Only `Normalization::Running` has any.
+pub fn mlp_buffers(mlp: &MLP) -> Vec<(String, Vec<usize>, Vec<f64>)> {
+    let mut buffers: Vec<(String, Vec<usize>, Vec<f64>)> = vec![];
+    if mlp.normalization() != Normalization::Running {
+        return buffers;
+    }
+    let values = mlp.buffers();
+    let mut offset = 0;
+    let mut input_size = mlp.inputs().len();
+    for (index, size) in mlp.sizes().iter().enumerate() {
+        for (kind, count) in [
+            ("running_mean", input_size),
+            ("running_var", input_size),
+            ("count", 1),
+        ] {
+            buffers.push((
+                format!("norms.{}.{}", index, kind),
+                vec![count],
+                values[offset..offset + count].to_vec(),
+            ));
+            offset += count;
+        }
+        input_size = *size;
+    }
+    assert_eq!(offset, values.len());
+    return buffers;
+}
+
+// Everything a weight file has to hold to reproduce `mlp`: the parameter tensors
+// of `mlp_tensors` with their values, followed by `mlp_buffers`.
+pub fn mlp_tensor_values(mlp: &MLP) -> Vec<(String, Vec<usize>, Vec<f64>)> {
+    let mut tensors: Vec<(String, Vec<usize>, Vec<f64>)> = mlp_tensors(mlp)
+        .into_iter()
+        .map(|tensor| {
+            let values = tensor.nodes.iter().map(|n| n.borrow().value).collect();
+            (tensor.name, tensor.shape, values)
+        })
+        .collect();
+    tensors.extend(mlp_buffers(mlp));
+    return tensors;
+}
+
+// Writes loaded `(name, shape, values)` tensors into `mlp`. Every tensor of
+// `mlp_tensor_values` must be present exactly once with its shape; nothing is
+// written otherwise.
+pub fn assign_mlp_tensors(
+    mlp: &mut MLP,
+    loaded: Vec<(String, Vec<usize>, Vec<f64>)>,
+) -> io::Result<()> {
+    let expected = mlp_tensor_values(mlp);
+    let mut values: Vec<Option<Vec<f64>>> = vec![None; expected.len()];
+    for (name, shape, data) in loaded {
+        let index = expected
+            .iter()
+            .position(|(expected_name, _, _)| *expected_name == name)
+            .ok_or_else(|| invalid(&format!("unexpected tensor {}", name)))?;
+        if shape != expected[index].1 {
+            return Err(invalid(&format!(
+                "{} has shape {:?}, the model expects {:?}",
+                name, shape, expected[index].1
+            )));
+        }
+        if data.len() != expected[index].2.len() {
+            return Err(invalid(&format!(
+                "{} has {} values for shape {:?}",
+                name,
+                data.len(),
+                shape
+            )));
+        }
+        if values[index].is_some() {
+            return Err(invalid(&format!("duplicate tensor {}", name)));
+        }
+        values[index] = Some(data);
+    }
+    if let Some(index) = values.iter().position(|v| v.is_none()) {
+        return Err(invalid(&format!("missing tensor {}", expected[index].0)));
+    }
+
+    let values: Vec<Vec<f64>> = values.into_iter().flatten().collect();
+    let tensors = mlp_tensors(mlp);
+    for (tensor, values) in tensors.iter().zip(values.iter()) {
+        for (node, value) in tensor.nodes.iter().zip(values.iter()) {
+            node.borrow_mut().value = *value;
+        }
+    }
+    mlp.set_buffers(&values[tensors.len()..].concat());
+    return Ok(());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::nn::Activation;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_mlp_tensors_cover_all_parameters() {
+        let mlp = MLP::with_normalization(
+            vec![3, 2],
+            vec![Activation::ReLU, Activation::Identity],
+            Normalization::Layer,
+            4,
+        );
+        let tensors = mlp_tensors(&mlp);
+        let names: Vec<&str> = tensors.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "layers.0.weight",
+                "layers.0.bias",
+                "layers.1.weight",
+                "layers.1.bias",
+                "norms.0.weight",
+                "norms.0.bias",
+                "norms.1.weight",
+                "norms.1.bias"
+            ]
+        );
+        assert_eq!(tensors[0].shape, vec![3, 4]);
+        assert_eq!(tensors[6].shape, vec![3]);
+
+        // row 1, column 2 of the first weight matrix is weight 2 of neuron 1
+        let named = mlp.named_parameters();
+        let (name, _) = named
+            .iter()
+            .find(|(_, node)| Rc::ptr_eq(node, &tensors[0].nodes[4 + 2]))
+            .unwrap();
+        assert_eq!(name, "layers.0.neurons.1.weight.2");
+    }
+
+    #[test]
+    fn test_running_statistics_are_exported() {
+        let mut mlp = MLP::with_normalization(
+            vec![3, 2],
+            vec![Activation::ReLU, Activation::Identity],
+            Normalization::Running,
+            4,
+        );
+        mlp.forward(&[1.0, 2.0, 3.0, 4.0]);
+        mlp.forward(&[-1.0, 0.0, 5.0, 2.0]);
+        let buffers = mlp_buffers(&mlp);
+        let names: Vec<&str> = buffers.iter().map(|b| b.0.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "norms.0.running_mean",
+                "norms.0.running_var",
+                "norms.0.count",
+                "norms.1.running_mean",
+                "norms.1.running_var",
+                "norms.1.count"
+            ]
+        );
+        assert_eq!(buffers[0].2, vec![0.0, 1.0, 4.0, 3.0]);
+        assert_eq!(buffers[2].2, vec![2.0]);
+
+        let mut copy = MLP::with_normalization(
+            vec![3, 2],
+            vec![Activation::ReLU, Activation::Identity],
+            Normalization::Running,
+            4,
+        );
+        let mut values = mlp_tensor_values(&mlp);
+        let last = values.pop().unwrap();
+        // nothing is written while a tensor is missing
+        assert!(assign_mlp_tensors(&mut copy, values.clone()).is_err());
+        assert_eq!(copy.buffers()[0], 0.0);
+        values.push((last.0.clone(), vec![1], vec![]));
+        assert!(assign_mlp_tensors(&mut copy, values.clone()).is_err());
+        values.pop();
+        values.push(last);
+        assign_mlp_tensors(&mut copy, values).unwrap();
+        assert_eq!(copy.buffers(), mlp.buffers());
+    }
+}
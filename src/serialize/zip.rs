@@ -0,0 +1,156 @@
+use std::io;
+
+use crate::serialize::binary::invalid;
+
+// Just enough of the zip format for uncompressed (stored) archives such as the
+// ones written by `numpy.savez`. Zip64 and compressed entries are rejected.
+const LOCAL_HEADER: u32 = 0x04034b50;
+const CENTRAL_HEADER: u32 = 0x02014b50;
+const END_OF_DIRECTORY: u32 = 0x06054b50;
+
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc: u32 = 0xffffffff;
+    for byte in bytes.iter() {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = match crc & 1 {
+                1 => (crc >> 1) ^ 0xedb88320,
+                _ => crc >> 1,
+            };
+        }
+    }
+    return !crc;
+}
+
+pub fn write_zip(entries: &[(String, Vec<u8>)]) -> Vec<u8> {
+    let mut bytes: Vec<u8> = vec![];
+    let mut directory: Vec<u8> = vec![];
+    for (name, data) in entries.iter() {
+        assert!(data.len() < u32::MAX as usize && name.len() < u16::MAX as usize);
+        let offset = bytes.len() as u32;
+        let crc = crc32(data);
+
+        // version, flags, method (stored), time, date, crc, sizes, name and extra length
+        let mut common: Vec<u8> = vec![];
+        common.extend_from_slice(&20u16.to_le_bytes());
+        common.extend_from_slice(&0u16.to_le_bytes());
+        common.extend_from_slice(&0u16.to_le_bytes());
+        common.extend_from_slice(&0u16.to_le_bytes());
+        common.extend_from_slice(&0x21u16.to_le_bytes());
+        common.extend_from_slice(&crc.to_le_bytes());
+        common.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        common.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        common.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        common.extend_from_slice(&0u16.to_le_bytes());
+
+        bytes.extend_from_slice(&LOCAL_HEADER.to_le_bytes());
+        bytes.extend_from_slice(&common);
+        bytes.extend_from_slice(name.as_bytes());
+        bytes.extend_from_slice(data);
+
+        directory.extend_from_slice(&CENTRAL_HEADER.to_le_bytes());
+        directory.extend_from_slice(&20u16.to_le_bytes());
+        directory.extend_from_slice(&common);
+        // comment length, disk, internal and external attributes, offset
+        directory.extend_from_slice(&0u16.to_le_bytes());
+        directory.extend_from_slice(&0u16.to_le_bytes());
+        directory.extend_from_slice(&0u16.to_le_bytes());
+        directory.extend_from_slice(&0u32.to_le_bytes());
+        directory.extend_from_slice(&offset.to_le_bytes());
+        directory.extend_from_slice(name.as_bytes());
+    }
+
+    let directory_offset = bytes.len() as u32;
+    bytes.extend_from_slice(&directory);
+    bytes.extend_from_slice(&END_OF_DIRECTORY.to_le_bytes());
+    bytes.extend_from_slice(&0u16.to_le_bytes());
+    bytes.extend_from_slice(&0u16.to_le_bytes());
+    bytes.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&directory_offset.to_le_bytes());
+    bytes.extend_from_slice(&0u16.to_le_bytes());
+    return bytes;
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> io::Result<u16> {
+    return bytes
+        .get(offset..offset + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| invalid("zip: truncated"));
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> io::Result<u32> {
+    return bytes
+        .get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| invalid("zip: truncated"));
+}
+
+pub fn read_zip(bytes: &[u8]) -> io::Result<Vec<(String, Vec<u8>)>> {
+    // the end record is 22 bytes plus a comment of at most 64 KiB
+    let lowest = bytes.len().saturating_sub(22 + u16::MAX as usize);
+    let end = (lowest..bytes.len().saturating_sub(21))
+        .rev()
+        .find(|offset| u32_at(bytes, *offset).ok() == Some(END_OF_DIRECTORY))
+        .ok_or_else(|| invalid("zip: no end of central directory"))?;
+    let count = u16_at(bytes, end + 10)? as usize;
+    let mut offset = u32_at(bytes, end + 16)? as usize;
+
+    let mut entries: Vec<(String, Vec<u8>)> = Vec::with_capacity(count);
+    for _ in 0..count {
+        if u32_at(bytes, offset)? != CENTRAL_HEADER {
+            return Err(invalid("zip: bad central directory entry"));
+        }
+        let method = u16_at(bytes, offset + 10)?;
+        let crc = u32_at(bytes, offset + 16)?;
+        let size = u32_at(bytes, offset + 20)?;
+        let name_length = u16_at(bytes, offset + 28)? as usize;
+        let extra_length = u16_at(bytes, offset + 30)? as usize;
+        let comment_length = u16_at(bytes, offset + 32)? as usize;
+        let local = u32_at(bytes, offset + 42)? as usize;
+        let name = bytes
+            .get(offset + 46..offset + 46 + name_length)
+            .ok_or_else(|| invalid("zip: truncated"))?;
+        let name = String::from_utf8(name.to_vec()).map_err(|_| invalid("zip: bad name"))?;
+        if method != 0 {
+            return Err(invalid(&format!("zip: {} is compressed", name)));
+        }
+        if size == u32::MAX || local == u32::MAX as usize {
+            return Err(invalid("zip: zip64 archives are not supported"));
+        }
+
+        if u32_at(bytes, local)? != LOCAL_HEADER {
+            return Err(invalid("zip: bad local header"));
+        }
+        let start =
+            local + 30 + u16_at(bytes, local + 26)? as usize + u16_at(bytes, local + 28)? as usize;
+        let data = bytes
+            .get(start..start + size as usize)
+            .ok_or_else(|| invalid("zip: truncated"))?;
+        if crc32(data) != crc {
+            return Err(invalid(&format!("zip: crc mismatch in {}", name)));
+        }
+        entries.push((name, data.to_vec()));
+        offset += 46 + name_length + extra_length + comment_length;
+    }
+    return Ok(entries);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_zip_round_trip() {
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+        let entries = vec![
+            ("a.npy".to_string(), vec![1, 2, 3]),
+            ("b.npy".to_string(), vec![]),
+        ];
+        let mut bytes = write_zip(&entries);
+        assert_eq!(read_zip(&bytes).unwrap(), entries);
+        bytes[30 + 5] ^= 1;
+        assert!(read_zip(&bytes).is_err());
+    }
+}